@@ -1,3 +1,6 @@
 fn main() {
+  // canonical lr(1) tables, whose states know exactly which tokens may follow,
+  // so syntax errors can list them
+  std::env::set_var("LALRPOP_LANE_TABLE", "disabled");
   lalrpop::process_root().unwrap();
 }
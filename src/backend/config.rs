@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::{Result, Write};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use koopa::ir::{
   Program,
   Function,
@@ -14,30 +14,49 @@ use koopa::ir::{
 use koopa::ir::entities::ValueData;
 
 use super::format::Format;
+use super::value::Value as AsmValue;
+use super::float;
+use crate::frontend::FloatSig;
 
 pub struct Config<'p> {
   program: &'p Program,
+  strings: &'p HashSet<Value>, // globals holding string literals
+  floats: &'p HashMap<Function, FloatSig>, // float params and results of functions
   cur_func: Option<Function>,
   value_table: HashMap<Value, String>, // global values
   alloc_size: (usize, usize, usize), // ra + local + args
-  alloc_table: HashMap<*const ValueData, (usize, bool)>, // local values
+  alloc_table: HashMap<*const ValueData, (usize, bool)>, // local values, params of the function and blocks
+  copy_area: usize, // offset of the slots args of a jump are staged in
   bbs_id: usize,
-  bbs_table: HashMap<BasicBlock, String>
+  bbs_table: HashMap<BasicBlock, String>,
+  opt: bool, // emit optimized asm
+  next_bb: Option<BasicBlock>, // basic block laid out after the current one
+  widened: HashSet<*const ValueData>, // words of doubles already given along with their pair
 }
 
 impl<'p> Config<'p> {
-  pub fn new(p: &'p Program) -> Self {
+  pub fn new(p: &'p Program, strings: &'p HashSet<Value>, floats: &'p HashMap<Function, FloatSig>, opt: bool) -> Self {
     Self {
       program: p,
+      strings,
+      floats,
       cur_func: None,
       value_table: HashMap::new(),
       alloc_size: (0, 0, 0),
       alloc_table: HashMap::new(),
+      copy_area: 0,
       bbs_id: 0,
       bbs_table: HashMap::new(),
+      opt,
+      next_bb: None,
+      widened: HashSet::new(),
     }
   }
 
+  pub fn opt(&self) -> bool {
+    self.opt
+  }
+
   pub fn program(&self) -> &'p Program {
     self.program
   }
@@ -46,7 +65,20 @@ impl<'p> Config<'p> {
     self.cur_func = Some(func);
   }
 
-  pub fn value(&self, value: Value) -> &ValueData {
+  pub fn cur_func(&self) -> Function {
+    self.cur_func.unwrap()
+  }
+
+  // where the [count] args of a call to [func] go
+  pub fn arg_slots(&self, func: Function, count: usize) -> Vec<AsmValue> {
+    float::arg_slots(&self.floats[&func].0, count)
+  }
+
+  pub fn returns_float(&self, func: Function) -> bool {
+    self.floats[&func].1
+  }
+
+  pub fn value(&self, value: Value) -> &'p ValueData {
     if let Some(func) = self.cur_func {
       self.program.func(func).dfg().value(value)
     } else {
@@ -56,7 +88,7 @@ impl<'p> Config<'p> {
 
   pub fn stk_frame_size(&self) -> usize {
     let size = self.alloc_size;
-    (size.0 + size.1 + size.2 + 15) / 16 * 16
+    (size.0 + size.1 + size.2).div_ceil(16) * 16
   }
 
   // Deal Global Values
@@ -64,6 +96,10 @@ impl<'p> Config<'p> {
     self.value_table.get(&value).unwrap()
   }
 
+  pub fn is_string(&self, value: Value) -> bool {
+    self.strings.contains(&value)
+  }
+
   pub fn new_value(&mut self, value: Value, name: String) {
     self.value_table.insert(value, name);
   }
@@ -73,10 +109,36 @@ impl<'p> Config<'p> {
     self.alloc_table.get(&(value as *const ValueData))
   }
 
+  pub fn copy_area(&self) -> usize {
+    self.copy_area
+  }
+
+  pub fn parent_bb(&self, value: Value) -> Option<BasicBlock> {
+    self.program.func(self.cur_func.unwrap()).layout().parent_bb(value)
+  }
+
+  // whether a word of a double is still to be given, it is then taken as given
+  pub fn widen(&mut self, value: &ValueData) -> bool {
+    self.widened.insert(value)
+  }
+
+  pub fn bb_params(&self, bb: BasicBlock) -> &'p [Value] {
+    self.program.func(self.cur_func.unwrap()).dfg().bb(bb).params()
+  }
+
   pub fn get_bb(&self, bb: &BasicBlock) -> &str {
     self.bbs_table.get(bb).unwrap()
   }
 
+  pub fn set_next_bb(&mut self, bb: Option<BasicBlock>) {
+    self.next_bb = bb;
+  }
+
+  // jumps to the next basic block can be omitted in optimized asm
+  pub fn falls_through(&self, bb: &BasicBlock) -> bool {
+    self.opt && self.next_bb == Some(*bb)
+  }
+
   pub fn new_temp_label(&mut self) -> String {
     let result = format!(
       ".L_{}_TEMP_{}", 
@@ -91,18 +153,41 @@ impl<'p> Config<'p> {
     // Local Allocs
     self.alloc_size = (0, 0, 0);
     self.alloc_table = HashMap::new();
-    // Ra & Args
+    self.widened = HashSet::new();
+    // Ra & Args, of calls out only
     for value in func.dfg().values().values() {
       if let ValueKind::Call(v) = value.kind() {
-        self.alloc_size.0 = 4;
-        if v.args().len() * 4 > self.alloc_size.2 + 8 * 4 {
-          self.alloc_size.2 = (v.args().len() - 8) * 4;
+        if float::is_inline(&self.program.func(v.callee()).name()[1..]) {
+          continue;
         }
+        self.alloc_size.0 = 4;
+        let slots = self.arg_slots(v.callee(), v.args().len());
+        let stack = slots.iter().filter(|slot| matches!(slot, AsmValue::Arg(i) if *i >= 8)).count();
+        self.alloc_size.2 = self.alloc_size.2.max(stack * 4);
       }
     }
-    // Local Value
+    // Local Value, params are used anywhere once allocs are promoted,
+    // so the ones passed in registers are saved as well
+    let slots = self.arg_slots(self.cur_func(), func.params().len());
+    let mut reg_args = Vec::new();
     for value in func.dfg().values().values() {
-      if value.kind().is_local_inst() && !value.used_by().is_empty() {
+      let is_param = match value.kind() {
+        ValueKind::FuncArgRef(arg) => match slots[arg.index()] {
+          AsmValue::Arg(i) if i >= 8 => false,
+          AsmValue::Arg(i) => {
+            reg_args.push((format!("a{}", i), false, value));
+            true
+          },
+          AsmValue::FArg(i) => {
+            reg_args.push((format!("fa{}", i), true, value));
+            true
+          },
+          _ => unreachable!(),
+        },
+        ValueKind::BlockArgRef(_) => true,
+        _ => false,
+      };
+      if (value.kind().is_local_inst() || is_param) && !value.used_by().is_empty() {
         let ptr = self.alloc_size.2 + self.alloc_size.1;
         match value.kind() {
           ValueKind::Alloc(_) => {
@@ -120,6 +205,23 @@ impl<'p> Config<'p> {
         }
       }
     }
+    // Copy Area, for the params of one block
+    let params = func.dfg().bbs().values().map(|bb| bb.params().len()).max().unwrap_or(0);
+    self.copy_area = self.alloc_size.2 + self.alloc_size.1;
+    self.alloc_size.1 += params * 4;
+    // Args on the stack, in the frame of the caller
+    let offset = self.stk_frame_size();
+    for &param in func.params() {
+      let value = func.dfg().value(param);
+      if let ValueKind::FuncArgRef(arg) = value.kind() {
+        if let AsmValue::Arg(i) = slots[arg.index()] {
+          if i >= 8 {
+            let is_ptr = matches!(value.ty().kind(), TypeKind::Pointer(_));
+            self.alloc_table.insert(value, (offset + 4 * (i - 8), is_ptr));
+          }
+        }
+      }
+    }
     // BBS
     self.bbs_id = 0;
     self.bbs_table = HashMap::new();
@@ -150,6 +252,14 @@ impl<'p> Config<'p> {
     if self.alloc_size.0 > 0 {
       format.sw("ra", "sp", offset - 4)?;
     }
+    for (reg, is_float, value) in reg_args {
+      if is_float {
+        Format::new(file).uop("fmv.x.w", "t0", &reg)?;
+        AsmValue::from(self.sp_offset(value)).load(file, "t0")?;
+      } else {
+        AsmValue::from(self.sp_offset(value)).load(file, &reg)?;
+      }
+    }
     Ok(())
   }
 
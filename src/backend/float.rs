@@ -0,0 +1,87 @@
+/*
+  floats in the ir are the bits of an i32, kept in integer registers and stack slots:
+  - operations on them are calls to intrinsics declared by the frontend,
+    lowered here to f-extension instructions on ft0 and ft1
+  - functions take and return floats in fa0.., as the hard-float abi (ilp32f) does
+  - a variadic arg is a double in two words, converted by libgcc without the d extension
+*/
+
+use std::fs::File;
+use std::io::Result;
+use super::format::Format;
+use super::value::Value;
+
+const INTRINSICS: [&str; 14] = [
+  "__fadd", "__fsub", "__fmul", "__fdiv",
+  "__feq", "__fne", "__flt", "__fle", "__fgt", "__fge",
+  "__itof", "__ftoi", "__dlo", "__dhi",
+];
+
+pub fn is_intrinsic(callee: &str) -> bool {
+  INTRINSICS.contains(&callee)
+}
+
+// an intrinsic lowered without calling out, leaving ra alone
+pub fn is_inline(callee: &str) -> bool {
+  is_intrinsic(callee) && !matches!(callee, "__dlo" | "__dhi")
+}
+
+// where [count] args go, of a function with the float params [floats]:
+// floats in fa0.. while they last, the others as ints in a0.. then on the stack,
+// args past the params are variadic ones, passed as ints
+pub fn arg_slots(floats: &[bool], count: usize) -> Vec<Value> {
+  let (mut fregs, mut words) = (0, 0);
+  (0..count).map(|i| {
+    if floats.get(i) == Some(&true) && fregs < 8 {
+      fregs += 1;
+      Value::FArg(fregs - 1)
+    } else {
+      words += 1;
+      Value::Arg(words - 1)
+    }
+  }).collect()
+}
+
+// a float in t0 promoted to double, for `__dlo` and `__dhi`, giving its low word in a0 and high one in a1
+pub fn widen(file: &mut File) -> Result<()> {
+  let mut format = Format::new(file);
+  format.uop("fmv.w.x", "fa0", "t0")?;
+  format.call("__extendsfdf2")
+}
+
+// an inline intrinsic on operands in t0 and t1, giving the result in t0
+pub fn intrinsic(file: &mut File, callee: &str) -> Result<()> {
+  let mut format = Format::new(file);
+  match callee {
+    "__itof" => {
+      format.uop("fcvt.s.w", "ft0", "t0")?;
+      return format.uop("fmv.x.w", "t0", "ft0");
+    },
+    // truncated toward zero, as C does
+    "__ftoi" => {
+      format.uop("fmv.w.x", "ft0", "t0")?;
+      return format.bop("fcvt.w.s", "t0", "ft0", "rtz");
+    },
+    _ => {},
+  }
+  format.uop("fmv.w.x", "ft0", "t0")?;
+  format.uop("fmv.w.x", "ft1", "t1")?;
+  match callee {
+    "__fadd" => format.bop("fadd.s", "ft0", "ft0", "ft1")?,
+    "__fsub" => format.bop("fsub.s", "ft0", "ft0", "ft1")?,
+    "__fmul" => format.bop("fmul.s", "ft0", "ft0", "ft1")?,
+    "__fdiv" => format.bop("fdiv.s", "ft0", "ft0", "ft1")?,
+    // comparisons give an int
+    "__feq" => return format.bop("feq.s", "t0", "ft0", "ft1"),
+    "__fne" => {
+      format.bop("feq.s", "t0", "ft0", "ft1")?;
+      return format.uop("seqz", "t0", "t0");
+    },
+    "__flt" => return format.bop("flt.s", "t0", "ft0", "ft1"),
+    "__fle" => return format.bop("fle.s", "t0", "ft0", "ft1"),
+    "__fgt" => return format.bop("flt.s", "t0", "ft1", "ft0"),
+    "__fge" => return format.bop("fle.s", "t0", "ft1", "ft0"),
+    _ => unreachable!(),
+  }
+  format.uop("fmv.x.w", "t0", "ft0")
+}
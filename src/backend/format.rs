@@ -34,7 +34,7 @@ impl<'io> Format<'io> {
   }
 
   pub fn addi(&mut self, dst: &str, src: &str, imm: i32) -> Result<()> {
-    if imm >= -2048 && imm <= 2047 {
+    if (-2048..=2047).contains(&imm) {
       writeln!(self.file, "\taddi {dst}, {src}, {imm}")
     } else {
       self.li("t6", imm)?;
@@ -42,13 +42,18 @@ impl<'io> Format<'io> {
     }
   }
 
+  // binary op with an immediate that fits in 12 bits
+  pub fn bopi(&mut self, op: &str, dst: &str, src: &str, imm: i32) -> Result<()> {
+    writeln!(self.file, "\t{op} {dst}, {src}, {imm}")
+  }
+
   pub fn muli(&mut self, dst: &str, src: &str, imm: i32) -> Result<()> {
     self.li("t6", imm)?;
     writeln!(self.file, "\tmul {dst}, {src}, t6")
   }
 
   pub fn sw(&mut self, src: &str, base: &str, offset: i32) -> Result<()> {
-    if offset >= -2048 && offset <= 2047 {
+    if (-2048..=2047).contains(&offset) {
       writeln!(self.file, "\tsw {src}, {offset}({base})")
     } else {
       self.addi("t6", base, offset)?;
@@ -57,7 +62,7 @@ impl<'io> Format<'io> {
   }
 
   pub fn lw(&mut self, dst: &str, base: &str, offset: i32) -> Result<()> {
-    if offset >= -2048 && offset <= 2047 {
+    if (-2048..=2047).contains(&offset) {
       writeln!(self.file, "\tlw {dst}, {offset}({base})")
     } else {
       self.addi("t6", base, offset)?;
@@ -69,6 +74,10 @@ impl<'io> Format<'io> {
     writeln!(self.file, "\tbnez {cond}, {label}")
   }
 
+  pub fn beqz(&mut self, cond: &str, label: &str) -> Result<()> {
+    writeln!(self.file, "\tbeqz {cond}, {label}")
+  }
+
   pub fn j(&mut self, label: &str) -> Result<()> {
     writeln!(self.file, "\tj {label}")
   }
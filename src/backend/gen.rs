@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::{Write, Result};
 
 use koopa::ir::{
-  Program, FunctionData, Value, TypeKind
+  Program, FunctionData, Value, TypeKind, BasicBlock
 };
 use koopa::ir::entities::{ ValueData, ValueKind };
 use koopa::ir::values::*;
@@ -10,6 +10,7 @@ use koopa::ir::values::*;
 use super::config::Config;
 use super::format::Format;
 use super::value::Value as AsmValue;
+use super::float;
 
 pub trait AsmGen {
   type Out;
@@ -19,17 +20,35 @@ pub trait AsmGen {
 impl AsmGen for Program {
   type Out = ();
   fn generate(&self, file: &mut File, config: &mut Config) -> Result<Self::Out> {
+    let mut strings = 0;
     for &value in self.inst_layout() {
       let data = self.borrow_value(value);
-      let name = &data.name().as_ref().unwrap()[1..];
+      // string literals get local labels, which take no name of a global
+      let name = if config.is_string(value) {
+        strings += 1;
+        format!(".Lstr{}", strings - 1)
+      } else {
+        data.name().as_ref().unwrap()[1..].to_string()
+      };
+      config.new_value(value, name.clone());
+
+      // an undefined initializer marks a global defined in another file
+      if let ValueKind::GlobalAlloc(alloc) = data.kind() {
+        if matches!(self.borrow_value(alloc.init()).kind(), ValueKind::Undef(_)) {
+          continue;
+        }
+      }
 
-      writeln!(file, "\t.data")?;
-      writeln!(file, "\t.globl {name}")?;
+      // string literals are never written, nor seen by other files
+      if config.is_string(value) {
+        writeln!(file, "\t.section .rodata")?;
+      } else {
+        writeln!(file, "\t.data")?;
+        writeln!(file, "\t.globl {name}")?;
+      }
       writeln!(file, "{name}:")?;
       data.generate(file, config)?;
       writeln!(file)?;
-
-      config.new_value(value, name.into());
     }
 
     for &func in self.func_layout() {
@@ -51,14 +70,20 @@ impl AsmGen for FunctionData {
     writeln!(file, "\t.globl {name}")?;
     writeln!(file, "{name}:")?;
 
-    config.prologue(file, &self)?;
+    config.prologue(file, self)?;
     
-    for (bb, bb_t) in self.layout().bbs() {
+    let mut bbs = self.layout().bbs().iter().peekable();
+    while let Some((bb, bb_t)) = bbs.next() {
       let bb_name = config.get_bb(bb);
       writeln!(file, "{bb_name}:")?;
+      config.set_next_bb(bbs.peek().map(|(&next, _)| next));
 
       for &inst in bb_t.insts().keys() {
-        self.dfg().value(inst).generate(file, config)?;
+        let value = self.dfg().value(inst);
+        if config.opt() && is_dead(value) {
+          continue;
+        }
+        value.generate(file, config)?;
       }
     }
     writeln!(file)
@@ -74,7 +99,8 @@ impl AsmGen for Value {
       let value = config.value(*self);
       let asmvalue = match value.kind() {
         ValueKind::Integer(i) => AsmValue::Const(i.value()),
-        ValueKind::FuncArgRef(i) => AsmValue::Arg(i.index()),
+        // left by promoted allocs never stored to
+        ValueKind::Undef(_) => AsmValue::Const(0),
         _ => AsmValue::from(config.sp_offset(value)),
       };
       Ok(asmvalue)
@@ -96,17 +122,17 @@ impl AsmGen for ValueData {
       },
       ValueKind::Load(v) => {
         let src = v.src().generate(file, config)?;
-        src.to(file, "t0", 0)?;
+        src.to(file, "t0")?;
         if src.is_ptr() {
           Format::new(file).lw("t0", "t0", 0)?;
         }
-        AsmValue::from(config.sp_offset(&self)).load(file, "t0")?;
+        AsmValue::from(config.sp_offset(self)).load(file, "t0")?;
       },
       ValueKind::Store(v) => {
-        v.value().generate(file, config)?.to(file, "t0", config.stk_frame_size())?;
+        v.value().generate(file, config)?.to(file, "t0")?;
         let dst = v.dest().generate(file, config)?;
         if dst.is_ptr() {
-          dst.to(file, "t1", 0)?;
+          dst.to(file, "t1")?;
           Format::new(file).sw("t0", "t1", 0)?;
         } else {
           dst.load(file, "t0")?;
@@ -114,13 +140,13 @@ impl AsmGen for ValueData {
       }
       ValueKind::GetPtr(v) => {
         let src = v.src().generate(file, config)?;
-        v.index().generate(file, config)?.to(file, "t1", 0)?;
+        v.index().generate(file, config)?.to(file, "t1")?;
         let size = match self.ty().kind() {
           TypeKind::Pointer(b) => b.size(),
           _ => unreachable!(),
         };
         if src.is_ptr() {
-          src.to(file, "t0", 0)?;
+          src.to(file, "t0")?;
         } else {
           src.addr_to(file, "t0")?;
         }
@@ -128,17 +154,17 @@ impl AsmGen for ValueData {
         let mut format = Format::new(file);
         format.muli("t1", "t1", size as i32)?;
         format.bop("add", "t0", "t0", "t1")?;
-        AsmValue::from(config.sp_offset(&self)).load(file, "t0")?;
+        AsmValue::from(config.sp_offset(self)).load(file, "t0")?;
       },
       ValueKind::GetElemPtr(v) => {
         let src = v.src().generate(file, config)?;
-        v.index().generate(file, config)?.to(file, "t1", 0)?;
+        v.index().generate(file, config)?.to(file, "t1")?;
         let size = match self.ty().kind() {
           TypeKind::Pointer(b) => b.size(),
           _ => unreachable!(),
         };
         if src.is_ptr() {
-          src.to(file, "t0", 0)?;
+          src.to(file, "t0")?;
         } else {
           src.addr_to(file, "t0")?;
         }
@@ -146,11 +172,19 @@ impl AsmGen for ValueData {
         let mut format = Format::new(file);
         format.muli("t1", "t1", size as i32)?;
         format.bop("add", "t0", "t0", "t1")?;
-        AsmValue::from(config.sp_offset(&self)).load(file, "t0")?;
+        AsmValue::from(config.sp_offset(self)).load(file, "t0")?;
       },
       ValueKind::Binary(v) => {
-        v.lhs().generate(file, config)?.to(file, "t0", 0)?;
-        v.rhs().generate(file, config)?.to(file, "t1", 0)?;
+        v.lhs().generate(file, config)?.to(file, "t0")?;
+        let rhs = v.rhs().generate(file, config)?;
+        if let (true, AsmValue::Const(imm)) = (config.opt(), &rhs) {
+          if let Some((op, imm)) = imm_op(v.op(), *imm) {
+            Format::new(file).bopi(op, "t0", "t0", imm)?;
+            AsmValue::from(config.sp_offset(self)).load(file, "t0")?;
+            return Ok(());
+          }
+        }
+        rhs.to(file, "t1")?;
         let mut format = Format::new(file);
         match v.op() {
           BinaryOp::Add => format.bop("add", "t0", "t0", "t1")?,
@@ -183,16 +217,45 @@ impl AsmGen for ValueData {
             format.uop("seqz", "t0", "t0")?;
           },
         }
-        AsmValue::from(config.sp_offset(&self)).load(file, "t0")?;
+        AsmValue::from(config.sp_offset(self)).load(file, "t0")?;
       },
+      ValueKind::Branch(v) if !v.true_args().is_empty() || !v.false_args().is_empty() => {
+        v.cond().generate(file, config)?.to(file, "t0")?;
+        let temp = &config.new_temp_label();
+        Format::new(file).beqz("t0", temp)?;
+        pass_args(file, config, v.true_bb(), v.true_args())?;
+        let mut format = Format::new(file);
+        format.j(config.get_bb(&v.true_bb()))?;
+        format.label(temp)?;
+        pass_args(file, config, v.false_bb(), v.false_args())?;
+        if !config.falls_through(&v.false_bb()) {
+          Format::new(file).j(config.get_bb(&v.false_bb()))?;
+        }
+        /*
+          beqz cond TEMP
+          (args of true)
+          j true
+        TEMP:
+          (args of false)
+          j false
+        */
+      }
       ValueKind::Branch(v) => {
-        v.cond().generate(file, config)?.to(file, "t0", 0)?;
+        v.cond().generate(file, config)?.to(file, "t0")?;
         let mut format = Format::new(file);
         let temp = &config.new_temp_label();
-        format.bnez("t0", temp)?;
-        format.j(config.get_bb(&v.false_bb()))?;
-        format.label(temp)?;
-        format.j(config.get_bb(&v.true_bb()))?;
+        if config.falls_through(&v.false_bb()) {
+          format.beqz("t0", temp)?;
+          format.j(config.get_bb(&v.true_bb()))?;
+          format.label(temp)?;
+        } else {
+          format.bnez("t0", temp)?;
+          format.j(config.get_bb(&v.false_bb()))?;
+          format.label(temp)?;
+          if !config.falls_through(&v.true_bb()) {
+            format.j(config.get_bb(&v.true_bb()))?;
+          }
+        }
         /*
           br cond TEMP
           j false
@@ -200,22 +263,45 @@ impl AsmGen for ValueData {
           j true
         */
       }
-      ValueKind::Jump(v) => Format::new(file).j(config.get_bb(&v.target()))?,
-      ValueKind::Call(v) => {
-        for (idx, arg) in v.args().iter().enumerate() {
-          arg.generate(file, config)?.to(file, "t0", 0)?;
-          AsmValue::Arg(idx).load(file, "t0")?;
+      ValueKind::Jump(v) => {
+        pass_args(file, config, v.target(), v.args())?;
+        if !config.falls_through(&v.target()) {
+          Format::new(file).j(config.get_bb(&v.target()))?;
         }
+      }
+      ValueKind::Call(v) => {
         let callee = &config.program().func(v.callee()).name()[1..];
+        if float::is_intrinsic(callee) && !float::is_inline(callee) {
+          return widen(file, config, self, v.args()[0]);
+        }
+        if float::is_intrinsic(callee) {
+          for (arg, reg) in v.args().iter().zip(["t0", "t1"]) {
+            arg.generate(file, config)?.to(file, reg)?;
+          }
+          float::intrinsic(file, callee)?;
+          AsmValue::from(config.sp_offset(self)).load(file, "t0")?;
+          return Ok(());
+        }
+        let slots = config.arg_slots(v.callee(), v.args().len());
+        for (arg, slot) in v.args().iter().zip(slots) {
+          arg.generate(file, config)?.to(file, "t0")?;
+          slot.load(file, "t0")?;
+        }
         Format::new(file).call(callee)?;
         if !self.used_by().is_empty() { // otherwise not in symbol table
-          AsmValue::from(config.sp_offset(&self)).load(file, "a0")?;
+          if config.returns_float(v.callee()) {
+            Format::new(file).uop("fmv.x.w", "a0", "fa0")?;
+          }
+          AsmValue::from(config.sp_offset(self)).load(file, "a0")?;
         }
       }
       ValueKind::GlobalAlloc(v) => config.program().borrow_value(v.init()).generate(file, config)?,
       ValueKind::Return(_v) => {
         if let Some(v) = _v.value() {
-          v.generate(file, config)?.to(file, "a0", 0)?;
+          v.generate(file, config)?.to(file, "a0")?;
+          if config.returns_float(config.cur_func()) {
+            Format::new(file).uop("fmv.w.x", "fa0", "a0")?;
+          }
         }
         config.epilogue(file)?;
       }
@@ -223,4 +309,87 @@ impl AsmGen for ValueData {
     }
     Ok(())
   }
+}
+
+// side-effect free instructions whose results are never used
+fn is_dead(value: &ValueData) -> bool {
+  value.used_by().is_empty() && matches!(
+    value.kind(),
+    ValueKind::Load(_) | ValueKind::GetPtr(_) | ValueKind::GetElemPtr(_) | ValueKind::Binary(_)
+  )
+}
+
+// the words of a float promoted to double, for this `__dlo` or `__dhi` and its pair
+// on the same float in the block, which is then already given
+fn widen(file: &mut File, config: &mut Config, value: &ValueData, float: Value) -> Result<()> {
+  if !config.widen(value) {
+    return Ok(());
+  }
+  let words = |config: &Config, user: Value| match config.value(user).kind() {
+    ValueKind::Call(call) => match &config.program().func(call.callee()).name()[1..] {
+      "__dlo" => Some("a0"),
+      "__dhi" => Some("a1"),
+      _ => None,
+    },
+    _ => None,
+  };
+  let users: Vec<_> = config.value(float).used_by().iter().copied().collect();
+  let this = *users.iter().find(|&&user| std::ptr::eq(config.value(user), value)).unwrap();
+  let pair = users.iter().copied().find(|&user| {
+    user != this
+      && words(config, user).is_some_and(|word| Some(word) != words(config, this))
+      && config.parent_bb(user) == config.parent_bb(this)
+  });
+
+  float.generate(file, config)?.to(file, "t0")?;
+  float::widen(file)?;
+  AsmValue::from(config.sp_offset(value)).load(file, words(config, this).unwrap())?;
+  if let Some(pair) = pair {
+    let data = config.value(pair);
+    if config.widen(data) {
+      AsmValue::from(config.sp_offset(data)).load(file, words(config, pair).unwrap())?;
+    }
+  }
+  Ok(())
+}
+
+// copy the args of a jump into the params of its target, staged through the copy area
+// when a param is also an arg, as the loop counter swapped with another is
+fn pass_args(file: &mut File, config: &mut Config, target: BasicBlock, args: &[Value]) -> Result<()> {
+  let params = config.bb_params(target);
+  let moves: Vec<_> = params.iter().zip(args).filter(|(param, arg)| param != arg).collect();
+  if moves.iter().any(|(_, arg)| params.contains(arg)) {
+    let area = config.copy_area() as i32;
+    for (i, (_, arg)) in moves.iter().enumerate() {
+      arg.generate(file, config)?.to(file, "t0")?;
+      Format::new(file).sw("t0", "sp", area + 4 * i as i32)?;
+    }
+    for (i, (param, _)) in moves.iter().enumerate() {
+      Format::new(file).lw("t0", "sp", area + 4 * i as i32)?;
+      param.generate(file, config)?.load(file, "t0")?;
+    }
+  } else {
+    for (param, arg) in moves {
+      arg.generate(file, config)?.to(file, "t0")?;
+      param.generate(file, config)?.load(file, "t0")?;
+    }
+  }
+  Ok(())
+}
+
+// binary operations with a 12-bit immediate form
+fn imm_op(op: BinaryOp, imm: i32) -> Option<(&'static str, i32)> {
+  let inst = match op {
+    BinaryOp::Add => ("addi", imm),
+    BinaryOp::Sub => ("addi", imm.checked_neg()?),
+    BinaryOp::And => ("andi", imm),
+    BinaryOp::Or => ("ori", imm),
+    BinaryOp::Xor => ("xori", imm),
+    BinaryOp::Lt => ("slti", imm),
+    BinaryOp::Shl => return Some(("slli", imm & 31)),
+    BinaryOp::Shr => return Some(("srli", imm & 31)),
+    BinaryOp::Sar => return Some(("srai", imm & 31)),
+    _ => return None,
+  };
+  (-2048..=2047).contains(&inst.1).then_some(inst)
 }
\ No newline at end of file
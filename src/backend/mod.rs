@@ -1,21 +1,33 @@
 /*
   backend of the compiler:
   - gen: generation for risc-v asm
-  - config: global configuration
+  - config: global configuration (and switches for optimized output)
   - value: deal ptr/alloc
   - format: output asm properly
+  - float: lower float intrinsics to the f extension
 */
 
 mod gen;
 mod config;
 mod format;
 mod value;
+mod float;
 
 use gen::AsmGen;
 use config::Config;
 use std::fs::File;
-use koopa::ir::Program;
+use std::collections::{HashMap, HashSet};
+use koopa::ir::{Function, Program, Value};
+use crate::frontend::FloatSig;
 
-pub fn generate_asm(program: &Program, path: &str) -> Result<(), std::io::Error> {
-  program.generate(&mut File::create(path)?, &mut Config::new(program))
+// [strings] are the globals holding string literals, kept read-only,
+// [floats] the float params and results of functions, passed in float registers
+pub fn generate_asm(
+  program: &Program,
+  strings: &HashSet<Value>,
+  floats: &HashMap<Function, FloatSig>,
+  path: &str,
+  opt: bool,
+) -> Result<(), std::io::Error> {
+  program.generate(&mut File::create(path)?, &mut Config::new(program, strings, floats, opt))
 }
\ No newline at end of file
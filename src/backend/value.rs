@@ -5,7 +5,8 @@ use super::format::Format;
 pub enum Value {
   Null,
   Const(i32),
-  Arg(usize),
+  Arg(usize), // of the call being made, in a0.. then on the stack
+  FArg(usize), // a float one, in fa0..
   Global(String),
   Local((usize, bool)),
 }
@@ -19,23 +20,15 @@ impl Value {
     }
   }
 
-  pub fn to(&self, file: &mut File, dst: &str, offset: usize) -> Result<()> {
+  pub fn to(&self, file: &mut File, dst: &str) -> Result<()> {
     let mut format = Format::new(file);
     match self {
       Self::Const(i) => format.li(dst, *i),
       Self::Local((o, _)) => format.lw(dst, "sp", *o as i32),
       Self::Global(sym) => {
-        format.la(dst, &sym)?;
+        format.la(dst, sym)?;
         format.lw(dst, dst, 0)
       },
-      Self::Arg(i) => {
-        if *i < 8 {
-          format.mv(dst, &format!("a{}", *i))?;
-        } else {
-          format.lw(dst, "sp", (offset + 4 * (*i - 8)) as i32)?;
-        }
-        Ok(())
-      },
       _ => unreachable!(),
     }
   }
@@ -44,7 +37,7 @@ impl Value {
     let mut format = Format::new(file);
     match self {
       Self::Local((o, _)) => format.addi(dst, "sp", *o as i32),
-      Self::Global(sym) => format.la(dst, &sym),
+      Self::Global(sym) => format.la(dst, sym),
       _ => unreachable!(),
     }
   }
@@ -61,9 +54,10 @@ impl Value {
         }
         Ok(())
       }
+      Self::FArg(i) => format.uop("fmv.w.x", &format!("fa{}", *i), src),
       Self::Local((o, _)) => format.sw(src, "sp", *o as i32),
       Self::Global(sym) => {
-        format.la("t5", &sym)?;
+        format.la("t5", sym)?;
         format.sw(src, "t5", 0)
       },
       _ => unreachable!()
@@ -0,0 +1,210 @@
+/*
+  rendering of compiler diagnostics:
+  - text: rustc-style report with source snippet and caret, optionally coloured
+  - json: one object per line, for editor integration
+*/
+
+use crate::frontend::{FrontendError, FrontendWarning, Span};
+
+use std::fmt::Write;
+
+#[derive(Clone, Copy)]
+pub enum Level {
+  Error,
+  Warning,
+  Note,
+}
+
+impl Level {
+  fn name(self) -> &'static str {
+    match self {
+      Self::Error => "error",
+      Self::Warning => "warning",
+      Self::Note => "note",
+    }
+  }
+
+  fn color(self) -> &'static str {
+    match self {
+      Self::Error => "\x1b[1;31m",
+      Self::Warning => "\x1b[1;33m",
+      Self::Note => "\x1b[1;32m",
+    }
+  }
+}
+
+pub struct Diagnostic {
+  pub level: Level,
+  pub message: String,
+  pub span: Option<Span>,
+  pub notes: Vec<(String, Option<Span>)>,
+}
+
+impl From<&FrontendError> for Diagnostic {
+  fn from(e: &FrontendError) -> Self {
+    let notes = match e {
+      FrontendError::MultiDef(_, _, Some(prev)) => {
+        vec![("previous definition here".into(), Some(*prev))]
+      },
+      FrontendError::MultiDef(ident, _, None) => {
+        vec![(format!("`{}` is provided by the runtime library", ident), None)]
+      },
+      FrontendError::ConflictingTypes(_, ty, _, Some(prev)) => {
+        vec![(format!("previously declared here as `{}`", ty), Some(*prev))]
+      },
+      FrontendError::ConflictingTypes(ident, ty, _, None) => {
+        vec![(format!("`{}` is provided by the runtime library as `{}`", ident, ty), None)]
+      },
+      FrontendError::ArgCountMismatch(ident, _, _, _, Some(def)) => {
+        vec![(format!("`{}` defined here", ident), Some(*def))]
+      },
+      FrontendError::DuplicateCase(_, _, prev) => vec![("previous label here".into(), Some(*prev))],
+      FrontendError::TooManyErrors => {
+        vec![("use -ferror-limit=0 to report all errors".into(), None)]
+      },
+      _ => vec![],
+    };
+    Self { level: Level::Error, message: e.to_string(), span: e.span(), notes }
+  }
+}
+
+impl Diagnostic {
+  // a warning names its flag, -Werror turns it into an error
+  pub fn from_warning(w: &FrontendWarning, as_error: bool) -> Self {
+    let (level, flag) = if as_error {
+      (Level::Error, format!("-Werror={}", w.name()))
+    } else {
+      (Level::Warning, format!("-W{}", w.name()))
+    };
+    let notes = match w {
+      FrontendWarning::Shadow(_, _, outer) => vec![("shadowed declaration is here".into(), Some(*outer))],
+      _ => vec![],
+    };
+    Self { level, message: format!("{} [{}]", w, flag), span: Some(w.span()), notes }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Text,
+  Json,
+}
+
+pub struct Renderer<'s> {
+  file: &'s str,
+  source: &'s str,
+  format: Format,
+  color: bool,
+}
+
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl<'s> Renderer<'s> {
+  pub fn new(file: &'s str, source: &'s str, format: Format, color: bool) -> Self {
+    Self { file, source, format, color }
+  }
+
+  // 1-based (line, column) of a byte offset
+  fn locate(&self, offset: usize) -> (usize, usize) {
+    let before = &self.source[..offset.min(self.source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+  }
+
+  fn paint(&self, text: &str, style: &str) -> String {
+    if self.color {
+      format!("{}{}{}", style, text, RESET)
+    } else {
+      text.into()
+    }
+  }
+
+  pub fn render(&self, diag: &Diagnostic) -> String {
+    match self.format {
+      Format::Text => self.render_text(diag),
+      Format::Json => self.render_json(diag),
+    }
+  }
+
+  fn render_text(&self, diag: &Diagnostic) -> String {
+    let mut out = String::new();
+    self.render_message(&mut out, diag.level, &diag.message, diag.span);
+    for (note, span) in &diag.notes {
+      self.render_message(&mut out, Level::Note, note, *span);
+    }
+    out
+  }
+
+  fn render_message(&self, out: &mut String, level: Level, message: &str, span: Option<Span>) {
+    let header = self.paint(level.name(), level.color());
+    writeln!(out, "{}{}", header, self.paint(&format!(": {}", message), BOLD)).unwrap();
+    let span = match span {
+      Some(span) => span,
+      None => return,
+    };
+
+    let (line, column) = self.locate(span.start);
+    let text = self.source.lines().nth(line - 1).unwrap_or("");
+    let gutter = " ".repeat(line.to_string().len());
+    writeln!(out, "{}{} {}:{}:{}", gutter, self.paint("-->", BLUE), self.file, line, column).unwrap();
+    writeln!(out, "{} {}", gutter, self.paint("|", BLUE)).unwrap();
+    writeln!(out, "{} {} {}", self.paint(&line.to_string(), BLUE), self.paint("|", BLUE), text).unwrap();
+
+    // underline to the end of the span or of the first line
+    let width = self.source[span.start.min(self.source.len())..span.end.min(self.source.len())]
+      .lines().next().map_or(0, |s| s.chars().count()).max(1);
+    let padding: String = text.chars().take(column - 1)
+      .map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let caret = self.paint(&"^".repeat(width), level.color());
+    writeln!(out, "{} {} {}{}", gutter, self.paint("|", BLUE), padding, caret).unwrap();
+  }
+
+  fn render_json(&self, diag: &Diagnostic) -> String {
+    let mut out = String::new();
+    write!(out, "{{\"level\":\"{}\",\"message\":{}", diag.level.name(), json_str(&diag.message)).unwrap();
+    self.render_json_span(&mut out, diag.span);
+    out.push_str(",\"notes\":[");
+    for (i, (note, span)) in diag.notes.iter().enumerate() {
+      if i > 0 {
+        out.push(',');
+      }
+      write!(out, "{{\"message\":{}", json_str(note)).unwrap();
+      self.render_json_span(&mut out, *span);
+      out.push('}');
+    }
+    out.push_str("]}\n");
+    out
+  }
+
+  fn render_json_span(&self, out: &mut String, span: Option<Span>) {
+    write!(out, ",\"file\":{}", json_str(self.file)).unwrap();
+    if let Some(span) = span {
+      let (line, column) = self.locate(span.start);
+      let (end_line, end_column) = self.locate(span.end);
+      write!(
+        out,
+        ",\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}",
+        line, column, end_line, end_column
+      ).unwrap();
+    }
+  }
+}
+
+fn json_str(s: &str) -> String {
+  let mut out = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
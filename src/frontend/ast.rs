@@ -1,3 +1,80 @@
+use std::fmt;
+
+// byte offsets [start, end) into the source file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+impl Span {
+  pub fn new(start: usize, end: usize) -> Self {
+    Self { start, end }
+  }
+}
+
+// index of a symbol in the table built by semantic analysis
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SymbolId(pub usize);
+
+// base type of declarations and array elements
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BType {
+  Int,
+  Float,
+}
+
+// types of symbols and expressions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ty {
+  Unknown, // not checked yet, or ill-typed
+  Void,
+  Int,
+  Float,
+  Array(BType, Vec<usize>), // int a[d1][d2]
+  Ptr(BType, Vec<usize>), // pointer to array, int a[][d2] as Ptr([d2]), int a[] as Ptr([])
+  Str, // string literal, only passed to lib functions
+  Func(Box<Ty>, Vec<Ty>, bool), // return type, param types, whether more args may follow
+}
+
+impl Ty {
+  // a declared var, an array if it has dims
+  pub fn decl(btype: BType, dims: Vec<usize>) -> Self {
+    if dims.is_empty() { btype.into() } else { Self::Array(btype, dims) }
+  }
+}
+
+impl From<BType> for Ty {
+  fn from(btype: BType) -> Self {
+    match btype {
+      BType::Int => Self::Int,
+      BType::Float => Self::Float,
+    }
+  }
+}
+
+impl fmt::Display for Ty {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let dims = |dims: &[usize]| dims.iter().map(|d| format!("[{}]", d)).collect::<String>();
+    match self {
+      Self::Unknown => write!(f, "{{unknown}}"),
+      Self::Void => write!(f, "void"),
+      Self::Int => write!(f, "int"),
+      Self::Float => write!(f, "float"),
+      Self::Array(btype, d) => write!(f, "{}{}", Ty::from(*btype), dims(d)),
+      Self::Ptr(btype, d) => write!(f, "{}[]{}", Ty::from(*btype), dims(d)),
+      Self::Str => write!(f, "string"),
+      Self::Func(ret, params, variadic) => {
+        let mut params: Vec<_> = params.iter().map(|p| p.to_string()).collect();
+        if *variadic {
+          params.push("...".into());
+        }
+        write!(f, "{}({})", ret, params.join(", "))
+      },
+    }
+  }
+}
+
 pub struct CompUnit {
   pub global_def: Vec<GlobalDef>,
 }
@@ -13,6 +90,7 @@ pub enum Decl {
 }
 
 pub struct ConstDecl {
+  pub btype: BType,
   pub item: Vec<ConstDef>,
 }
 
@@ -20,6 +98,8 @@ pub struct ConstDef {
   pub ident: String,
   pub dims: Vec<ConstExp>,
   pub initial: ConstInitVal,
+  pub span: Span,
+  pub symbol: Option<SymbolId>,
 }
 
 pub enum ConstInitVal {
@@ -28,13 +108,17 @@ pub enum ConstInitVal {
 }
 
 pub struct VarDecl {
+  pub btype: BType,
   pub item: Vec<VarDef>,
+  pub is_extern: bool, // items without initializer are defined elsewhere
 }
 
 pub struct VarDef {
   pub ident: String,
   pub dims: Vec<ConstExp>,
   pub initial: Option<InitVal>,
+  pub span: Span,
+  pub symbol: Option<SymbolId>,
 }
 
 pub enum InitVal {
@@ -46,17 +130,32 @@ pub struct FuncDef {
   pub func_type: FuncType,
   pub ident: String,
   pub params: Vec<FuncFParam>,
-  pub block: Block,
+  pub block: Option<Block>, // [None] for a prototype
+  pub span: Span, // function header only
+  pub symbol: Option<SymbolId>,
 }
 
 pub enum FuncType {
   Int,
+  Float,
   Void,
 }
 
+impl From<BType> for FuncType {
+  fn from(btype: BType) -> Self {
+    match btype {
+      BType::Int => Self::Int,
+      BType::Float => Self::Float,
+    }
+  }
+}
+
 pub struct FuncFParam {
+  pub btype: BType,
   pub ident: String,
   pub dims: Option<Vec<ConstExp>>,
+  pub span: Span,
+  pub symbol: Option<SymbolId>,
 }
 
 pub struct Block {
@@ -68,12 +167,19 @@ pub enum BlockItem {
   Stmt(Stmt),
 }
 
-pub enum Stmt {
-  Assign(Assign),
+pub struct Stmt {
+  pub kind: StmtKind,
+  pub span: Span,
+}
+
+pub enum StmtKind {
   ExpStmt(ExpStmt),
   Block(Block),
   If(Box<If>),
   While(Box<While>),
+  For(Box<For>),
+  DoWhile(Box<DoWhile>),
+  Switch(Box<Switch>),
   Break(Break),
   Continue(Continue),
   Return(Option<Exp>),
@@ -81,6 +187,7 @@ pub enum Stmt {
 
 pub struct Assign {
   pub lval: LVal,
+  pub op: Option<BinaryOp>, // compound assignment, [None] for `=`
   pub exp: Exp,
 }
 
@@ -99,16 +206,73 @@ pub struct While {
   pub stmt: Stmt,
 }
 
+pub struct For {
+  pub init: Option<BlockItem>, // scoped to the loop
+  pub cond: Option<Exp>, // [None] loops forever
+  pub step: Option<Exp>,
+  pub stmt: Stmt,
+}
+
+pub struct DoWhile {
+  pub stmt: Stmt,
+  pub cond: Exp,
+}
+
+pub struct Switch {
+  pub cond: Exp,
+  pub cases: Vec<Case>, // sharing one scope
+}
+
+pub struct Case {
+  pub value: Option<ConstExp>, // [None] for default
+  pub items: Vec<BlockItem>,
+  pub span: Span, // label only
+}
+
 pub struct Break;
 
 pub struct Continue;
 
-pub enum Exp {
-  Number(i32),
+pub struct Exp {
+  pub kind: ExpKind,
+  pub span: Span,
+  pub ty: Ty, // filled in by semantic analysis
+  pub value: Option<i32>, // value of a constant expression, floats as their bits
+}
+
+impl Exp {
+  pub fn new(kind: ExpKind, span: Span) -> Self {
+    Self { kind, span, ty: Ty::Unknown, value: None }
+  }
+
+  // truth of a constant, a float is compared with zero rather than by its bits
+  pub fn truth(&self) -> Option<bool> {
+    let v = self.value?;
+    Some(match self.ty {
+      Ty::Float => f32::from_bits(v as u32) != 0.0,
+      _ => v != 0,
+    })
+  }
+}
+
+pub enum ExpKind {
+  Number(i64), // as written, range checked by semantic analysis
+  FloatNumber(f32),
+  Str(Vec<u8>), // with escapes resolved, without the terminating nul
   LVal(LVal),
   UnaryExp(UnaryOp, Box<Exp>),
-  FuncCall(String, Vec<Exp>),
+  FuncCall(FuncCall),
   BinaryExp(Box<Exp>, BinaryOp, Box<Exp>),
+  Assign(Box<Assign>),
+  IncDec(LVal, IncDec),
+  Comma(Box<Exp>, Box<Exp>),
+  Cond(Box<Exp>, Box<Exp>, Box<Exp>), // only one of the branches is evaluated
+}
+
+pub struct FuncCall {
+  pub ident: String,
+  pub args: Vec<Exp>,
+  pub symbol: Option<SymbolId>,
 }
 
 pub struct ConstExp {
@@ -118,11 +282,21 @@ pub struct ConstExp {
 pub struct LVal {
   pub ident: String,
   pub indices: Vec<Exp>,
+  pub span: Span,
+  pub symbol: Option<SymbolId>,
 }
 
 pub enum UnaryOp {
   Neg,
-  Not
+  Not,
+  BitNot,
+}
+
+pub enum IncDec {
+  PreInc,
+  PreDec,
+  PostInc,
+  PostDec,
 }
 
 pub enum BinaryOp {
@@ -133,6 +307,9 @@ pub enum BinaryOp {
   // AddExp
   Add,
   Sub,
+  // ShiftExp
+  Shl,
+  Shr,
   // RelExp
   Lt,
   Le,
@@ -141,6 +318,10 @@ pub enum BinaryOp {
   // EqExp
   Eq,
   Neq,
+  // BitAndExp, BitXorExp, BitOrExp
+  BitAnd,
+  BitXor,
+  BitOr,
   // AndExp
   And,
   // OrExp
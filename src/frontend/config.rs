@@ -1,5 +1,7 @@
-use super::gen::Result;
-use super::FrontendError;
+use super::ast::{SymbolId, Ty};
+use super::sema::Symbol;
+
+use super::FloatSig;
 
 use std::collections::HashMap;
 use koopa::ir::builder_traits::*;
@@ -16,52 +18,62 @@ use koopa::ir::{
   Function as IrFunction, 
 };
 
-// value stored in symbol table
-#[derive(Clone, Copy)]
-pub enum Value {
-  Const(i32),
-  Value(IrValue),
-}
-
 // information about (current) function
 #[derive(Clone, Copy)]
 pub struct Function {
+  id: SymbolId,
   ident: IrFunction,
+  entry: BasicBlock,
   current: BasicBlock,
   end: BasicBlock,
   ret_val: Option<IrValue>,
+  last_alloc: Option<IrValue>, // allocs are kept together at the start of the entry
 }
 
 pub struct Config<'p> {
   program: &'p mut Program,
+  symbols: &'p [Symbol], // symbol table from semantic analysis
   pub function: Option<Function>, // current function info, [None] for global config
-  pub vardef: Vec<HashMap<&'p str, Value>>, // symbol table for var defs
-  pub funcdef: HashMap<&'p str, IrFunction>, // symbol table for function defs
-  pub while_block: Vec<(BasicBlock, BasicBlock)>, // basic block chains for (while_entry, while_end)
+  pub vardef: HashMap<SymbolId, IrValue>, // allocations of var defs (scalar consts are inlined)
+  pub funcdef: HashMap<SymbolId, IrFunction>, // function defs and lib functions
+  pub libdef: HashMap<&'p str, IrFunction>, // lib functions and intrinsics by name
+  pub strings: HashMap<Vec<u8>, IrValue>, // globals of string literals
+  pub floats: HashMap<IrFunction, FloatSig>, // float params and result of each function, all i32 in the ir
+  pub loop_block: Vec<(Option<BasicBlock>, BasicBlock)>, // (continue, break) targets, a switch has no continue target
 }
 
 // Global Properties
 
 impl<'p> Config<'p> {
-  pub fn new(program: &'p mut Program) -> Self {
+  pub fn new(program: &'p mut Program, symbols: &'p [Symbol]) -> Self {
     Self {
-      program: program,
+      program,
+      symbols,
       function: None,
-      vardef: vec![HashMap::new()],
+      vardef: HashMap::new(),
       funcdef: HashMap::new(),
-      while_block: vec![],
+      libdef: HashMap::new(),
+      strings: HashMap::new(),
+      floats: HashMap::new(),
+      loop_block: vec![],
     }
   }
 
+  pub fn symbols(&self) -> &'p [Symbol] {
+    self.symbols
+  }
+
+  pub fn symbol(&self, id: SymbolId) -> &'p Symbol {
+    &self.symbols[id.0]
+  }
+
   pub fn is_global(&self) -> bool {
     self.function.is_none()
   }
 
   pub fn is_void(&self, func: IrFunction) -> bool {
     match self.program.func(func).ty().kind() {
-      TypeKind::Function(_, t) => {
-        return t.is_unit();
-      },
+      TypeKind::Function(_, t) => t.is_unit(),
       _ => unreachable!(),
     }
   }
@@ -101,6 +113,24 @@ impl<'p> Config<'p> {
     self.program.new_value()
   }
 
+  // a string literal as global data, nul terminated and packed four chars to a word,
+  // shared by equal literals, the backend keeps the globals in [strings] read-only
+  pub fn new_string(&mut self, s: &[u8]) -> IrValue {
+    if let Some(&string) = self.strings.get(s) {
+      return string;
+    }
+    let mut bytes = s.to_vec();
+    bytes.resize(s.len() / 4 * 4 + 4, 0);
+    let words = bytes.chunks(4)
+      .map(|w| self.program.new_value().integer(i32::from_le_bytes([w[0], w[1], w[2], w[3]])))
+      .collect();
+    let init = self.program.new_value().aggregate(words);
+    let string = self.program.new_value().global_alloc(init);
+    self.program.set_value_name(string, Some(format!("@__str_{}", self.strings.len())));
+    self.strings.insert(s.to_vec(), string);
+    string
+  }
+
   pub fn set_name(&mut self, value: IrValue, ident: &str) {
     if self.is_global() {
       self.program.set_value_name(value, Some(format!("@{}", ident)));
@@ -121,14 +151,37 @@ impl<'p> Config<'p> {
     self.function.unwrap().ret_val
   }
 
-  // enter a new function
-  pub fn enter_func(
-    &mut self,
-    name: &'p str,
-    params: Vec<Type>,
-    ret_ty: Type,
-  ) -> Result<()> {
-    let mut func_data = FunctionData::new(format!("@{}", name), params, ret_ty.clone());
+  pub fn ret_ty(&self) -> &'p Ty {
+    match &self.symbol(self.function.unwrap().id).ty {
+      Ty::Func(ret_ty, ..) => ret_ty,
+      _ => unreachable!(),
+    }
+  }
+
+  // declare a function once, before any body is generated, the body may come from another file
+  pub fn declare_func(&mut self, id: SymbolId, name: &str, params: Vec<Type>, ret_ty: Type) {
+    let program = &mut self.program;
+    let func = *self.funcdef.entry(id)
+      .or_insert_with(|| program.new_func(FunctionData::new(format!("@{}", name), params, ret_ty)));
+    self.new_float_sig(id, func);
+  }
+
+  // the params and result of a function that are floats, for the backend to pass in float registers
+  fn new_float_sig(&mut self, id: SymbolId, func: IrFunction) {
+    if let Ty::Func(ret_ty, params, _) = &self.symbol(id).ty {
+      let params = params.iter().map(|ty| *ty == Ty::Float).collect();
+      self.floats.insert(func, (params, **ret_ty == Ty::Float));
+    }
+  }
+
+  // enter a function declared before, to generate its body
+  pub fn enter_func(&mut self, id: SymbolId) {
+    let ident = self.funcdef[&id];
+    let ret_ty = match self.program.func(ident).ty().kind() {
+      TypeKind::Function(_, ret_ty) => ret_ty.clone(),
+      _ => unreachable!(),
+    };
+    let func_data = self.program.func_mut(ident);
     let entry = func_data.dfg_mut().new_bb().basic_block(Some("%func_entry".into()));
     let end = func_data.dfg_mut().new_bb().basic_block(Some("%func_end".into()));
     func_data.layout_mut().bbs_mut().push_key_back(entry).unwrap();
@@ -145,13 +198,7 @@ impl<'p> Config<'p> {
       }
     };
 
-    let ident = self.program.new_func(func_data);
-    self.function = Some(Function { ident, current: entry, end, ret_val });
-    self.new_func(name, ident)?;
-
-    // enter in a new scope
-    self.scope_in();
-    Ok(())
+    self.function = Some(Function { id, ident, entry, current: entry, end, ret_val, last_alloc: ret_val });
   }
 
   // return before leave
@@ -175,87 +222,62 @@ impl<'p> Config<'p> {
       self.insert_instr(ret);
     }
 
-    // leave out current scope
-    self.scope_out();
-
     // set current function as none
     self.function = None;
   }
 }
 
-// Scope, Symbol Table & Basic Blocks Implementation
+// Symbol Table & Basic Blocks Implementation
 
 impl<'p> Config<'p> {
-  // enter in a new scope
-  pub fn scope_in(&mut self) {
-    self.vardef.push(HashMap::new());
+  // insert new value definition
+  pub fn new_value(&mut self, id: SymbolId, value: IrValue) {
+    self.vardef.insert(id, value);
   }
 
-  // leave out current scope
-  pub fn scope_out(&mut self) {
-    self.vardef.pop();
+  // retrieve the value of a symbol
+  pub fn get_value(&self, id: SymbolId) -> IrValue {
+    self.vardef[&id]
   }
 
-  // insert new value definition into symbol table
-  pub fn new_value(&mut self, id: &'p str, value: Value) -> Result<()> {
-    let is_global = self.is_global();
-    let symbol_table = self.vardef.last_mut().unwrap();
-    if symbol_table.contains_key(id) || (is_global && self.funcdef.contains_key(id)) {
-      Err(FrontendError::MultiDef(id.into()))
-    } else {
-      symbol_table.insert(id, value);
-      Ok(())
-    }
+  // create new declaration for a library function
+  pub fn new_decl(&mut self, id: SymbolId, name: &'p str, params: Vec<Type>, ret_ty: Type) {
+    let func = self.program.new_func(FunctionData::new(format!("@{}", name), params, ret_ty));
+    self.funcdef.insert(id, func);
+    self.libdef.insert(name, func);
+    self.new_float_sig(id, func);
   }
 
-  // retrieve an value by ident 
-  pub fn get_value(&self, id: &str) -> Result<Value> {
-    let mut index = (self.vardef.len() - 1) as i32;
-    while index >= 0 {
-      if let Some(v) = self.vardef[index as usize].get(id) {
-        return Ok(v.clone());
-      }
-      index -= 1;
-    }
-    Err(FrontendError::UndeclaredId(id.into()))
-  }
-
-  // insert new function definition into symbol table
-  pub fn new_func(&mut self, id: &'p str, func: IrFunction) -> Result<()> {
-    if self.funcdef.contains_key(id) || self.vardef.first().unwrap().contains_key(id) {
-      Err(FrontendError::MultiDef(id.into()))
-    } else {
-      self.funcdef.insert(id, func);
-      Ok(())
-    }
-  }
-
-  // create new declaration
-  pub fn new_decl(&mut self, id: &'p str, params: Vec<Type>, ret_ty: Type) -> Result<()> {
-    let func = self.program.new_func(FunctionData::new(format!("@{}", id), params, ret_ty));
-    self.new_func(id, func)
+  // call an intrinsic for a float operation, declared along with the lib functions
+  pub fn float_op(&mut self, name: &str, args: Vec<IrValue>) -> IrValue {
+    let func = self.libdef[name];
+    let call = self.new_value_builder().call(func, args);
+    self.insert_instr(call);
+    call
   }
   
-  // retrieve a function by ident
-  pub fn get_func(&mut self, id: &str) -> Result<IrFunction> {
-    self.funcdef.get(id).copied().ok_or(FrontendError::UndeclaredId(id.into()))
+  // retrieve a function by symbol
+  pub fn get_func(&self, id: SymbolId) -> IrFunction {
+    self.funcdef[&id]
   }
 
-  // methods for while loop blocks
-  pub fn while_in(&mut self, bb_entry: BasicBlock, bb_end: BasicBlock) {
-    self.while_block.push((bb_entry, bb_end));
+  // methods for loop and switch blocks
+  pub fn loop_in(&mut self, bb_continue: Option<BasicBlock>, bb_break: BasicBlock) {
+    self.loop_block.push((bb_continue, bb_break));
   }
 
-  pub fn while_out(&mut self) {
-    self.while_block.pop();
+  pub fn loop_out(&mut self) {
+    self.loop_block.pop();
   }
 
+  // only inside loops or switches, checked by semantic analysis
   pub fn break_bb(&mut self) -> BasicBlock {
-    self.while_block.last().unwrap().1
+    self.loop_block.last().unwrap().1
   }
 
+  // the innermost loop, skipping switches
   pub fn continue_bb(&mut self) -> BasicBlock {
-    self.while_block.last().unwrap().0
+    self.loop_block.iter().rev().find_map(|(bb, _)| *bb).unwrap()
   }
 
   // create a new basic block in current function
@@ -274,4 +296,18 @@ impl<'p> Config<'p> {
   pub fn insert_instr(&mut self, instr: IrValue) {
     self.insts_mut().push_key_back(instr).unwrap();
   }
+
+  // create a local in the entry block, whatever the current one,
+  // so that it dominates all its uses even if its declaration is jumped over
+  pub fn new_alloc(&mut self, ty: Type) -> IrValue {
+    let alloc = self.new_value_builder().alloc(ty);
+    let func = self.function.unwrap();
+    let insts = self.layout_mut().bb_mut(func.entry).insts_mut();
+    match func.last_alloc {
+      Some(last) => insts.cursor_mut(last).insert_key_after(alloc).unwrap(),
+      None => insts.push_key_front(alloc).unwrap(),
+    }
+    self.function.as_mut().unwrap().last_alloc = Some(alloc);
+    alloc
+  }
 }
\ No newline at end of file
@@ -1,50 +1,188 @@
-// use super::gen::{ProgramGen};
-use super::config::{Value, Config};
 use crate::frontend::ast::*;
 
-impl Exp {
-  pub fn eval<'ast>(&'ast self, config: &mut Config<'ast>) -> Option<i32> {
-    match self {
-      Self::Number(v) => Some(*v),
-      Self::LVal(lval) => {
-        let v = config.get_value(&lval.ident).ok();
-        if let Some(x) = &v {
-          match x {
-            Value::Const(c) => Some(*c),
-            _ => None,
-          }
-        } else {
-          None
-        }
-      }
-      Self::UnaryExp(uop, exp) => {
-        match uop {
-          UnaryOp::Neg => Some(-exp.eval(config)?),
-          UnaryOp::Not => Some((exp.eval(config)? == 0).into()),
-        }
+// value of an int literal, hex and octal ones wrap to 32 bits as unsigned ones do in C,
+// values too large even for 64 bits saturate, semantic analysis rejects them anyway
+pub fn int_literal(digits: &str, radix: u32) -> i64 {
+  let value = u64::from_str_radix(digits, radix).unwrap_or(u64::MAX);
+  match u32::try_from(value) {
+    Ok(v) if radix != 10 => v as i32 as i64,
+    _ => value.min(i64::MAX as u64) as i64,
+  }
+}
+
+// value of a negated int literal that does not fit itself,
+// as INT_MIN is written `-2147483648`
+pub fn negated_int_literal(exp: &Exp) -> Option<i32> {
+  match exp.kind {
+    ExpKind::Number(v) if i32::try_from(v).is_err() => i32::try_from(-v).ok(),
+    _ => None,
+  }
+}
+
+// value of a hex float literal without its `0x`, e.g. `1.8p1` for 3.0,
+// rounded to nearest even once, from the exact value of its digits
+pub fn hex_float_literal(literal: &str) -> f32 {
+  let (mantissa, exp) = literal.split_once(['p', 'P']).unwrap();
+  let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+  // exponents out of the range of floats only matter by their sign
+  let exp = exp.parse::<i64>().unwrap_or(if exp.starts_with('-') { -100_000 } else { 100_000 });
+  let mut exp = exp.clamp(-100_000, 100_000) - 4 * frac.len() as i64;
+  // the leading digits exactly, the others only by whether any is not zero
+  let (mut bits, mut sticky) = (0u64, false);
+  for d in int.chars().chain(frac.chars()).map(|c| c.to_digit(16).unwrap() as u64) {
+    if bits >> 56 == 0 {
+      bits = bits * 16 + d;
+    } else {
+      sticky |= d != 0;
+      exp += 4;
+    }
+  }
+  if bits == 0 {
+    return 0.0;
+  }
+
+  // the value is in [2^e, 2^(e+1)), with 24 significant bits if normal, fewer if not
+  let top = 63 - bits.leading_zeros() as i64;
+  let e = top + exp;
+  if e > 127 {
+    return f32::INFINITY;
+  } else if e < -150 {
+    return 0.0;
+  }
+  let keep = 24 - (-126 - e).max(0);
+  let shift = top + 1 - keep;
+  let mut kept = if shift > 0 { bits >> shift } else { bits << -shift };
+  if shift > 0 {
+    let (rest, half) = (bits & ((1 << shift) - 1), 1 << (shift - 1));
+    if rest > half || (rest == half && (sticky || kept & 1 == 1)) {
+      kept += 1;
+    }
+  }
+  // exact in a double, and in a float unless rounded up past the largest one
+  (kept as f64 * 2f64.powi((exp + shift) as i32)) as f32
+}
+
+// bytes of a string literal without its quotes, unknown escapes stand for the char itself
+pub fn string_literal(literal: &str) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  let mut rest = literal.as_bytes();
+  while let Some((&c, tail)) = rest.split_first() {
+    rest = tail;
+    if c != b'\\' {
+      bytes.push(c);
+      continue;
+    }
+    let escaped = rest;
+    let (&e, tail) = escaped.split_first().unwrap();
+    rest = tail;
+    // the longest run of at most [max] digits, and its value
+    let number = |rest: &[u8], max: usize, radix: u32| {
+      let digits: Vec<_> = rest.iter().take(max).map_while(|d| (*d as char).to_digit(radix)).collect();
+      (digits.len(), digits.iter().fold(0u32, |v, d| v.wrapping_mul(radix).wrapping_add(*d)) as u8)
+    };
+    let c = match e {
+      b'n' => b'\n',
+      b't' => b'\t',
+      b'r' => b'\r',
+      b'a' => 0x07,
+      b'b' => 0x08,
+      b'f' => 0x0c,
+      b'v' => 0x0b,
+      b'0'..=b'7' => {
+        let (n, value) = number(escaped, 3, 8);
+        rest = &escaped[n..];
+        value
       },
-      Self::BinaryExp(lhs, op, rhs) => {
-        match op {
-          BinaryOp::Mul => Some(lhs.eval(config)? * rhs.eval(config)?),
-          BinaryOp::Div => (rhs.eval(config)).and_then(|rv| Some(lhs.eval(config)? / rv)),
-          BinaryOp::Mod => (rhs.eval(config)).and_then(|rv| Some(lhs.eval(config)? % rv)),
-          BinaryOp::Add => Some(lhs.eval(config)? + rhs.eval(config)?),
-          BinaryOp::Sub => Some(lhs.eval(config)? - rhs.eval(config)?),
-          BinaryOp::Lt => Some((lhs.eval(config)? < rhs.eval(config)?).into()),
-          BinaryOp::Gt => Some((lhs.eval(config)? > rhs.eval(config)?).into()),
-          BinaryOp::Le => Some((lhs.eval(config)? <= rhs.eval(config)?).into()),
-          BinaryOp::Ge => Some((lhs.eval(config)? >= rhs.eval(config)?).into()),
-          BinaryOp::Eq => Some((lhs.eval(config)? == rhs.eval(config)?).into()),
-          BinaryOp::Neq => Some((lhs.eval(config)? != rhs.eval(config)?).into()),
-          BinaryOp::And => (lhs.eval(config)).and_then(|lv| 
-            if lv == 0 { Some(0) } else { Some((rhs.eval(config)? != 0).into()) }
-          ),
-          BinaryOp::Or => (lhs.eval(config)).and_then(|lv|
-            if lv != 0 { Some(1) } else { Some((rhs.eval(config)? != 0).into()) }
-          ),
-        }
+      b'x' if number(rest, usize::MAX, 16).0 > 0 => {
+        let (n, value) = number(rest, usize::MAX, 16);
+        rest = &rest[n..];
+        value
       },
-      _ => None,
-    }
+      // \\, \", \', \? and the unknown ones
+      e => e,
+    };
+    bytes.push(c);
+  }
+  bytes
+}
+
+// a constant of type [from] as one of type [to], floats are carried as their bits
+pub fn convert(v: i32, from: &Ty, to: &Ty) -> i32 {
+  match (from, to) {
+    (Ty::Int, Ty::Float) => (v as f32).to_bits() as i32,
+    // truncated toward zero, saturating where C leaves it undefined
+    (Ty::Float, Ty::Int) => f32::from_bits(v as u32) as i32,
+    _ => v,
+  }
+}
+
+// value of a unary expression on a constant
+pub fn eval_unary(uop: &UnaryOp, v: Option<i32>) -> Option<i32> {
+  match uop {
+    UnaryOp::Neg => Some(v?.wrapping_neg()),
+    UnaryOp::Not => Some((v? == 0).into()),
+    UnaryOp::BitNot => Some(!v?),
+  }
+}
+
+// value of a binary expression, with short circuit for `&&` and `||`
+pub fn eval_binary(op: &BinaryOp, lv: Option<i32>, rv: Option<i32>) -> Option<i32> {
+  match op {
+    BinaryOp::And => if lv? == 0 { Some(0) } else { Some((rv? != 0).into()) },
+    BinaryOp::Or => if lv? != 0 { Some(1) } else { Some((rv? != 0).into()) },
+    _ => {
+      let (lv, rv) = (lv?, rv?);
+      match op {
+        BinaryOp::Mul => Some(lv.wrapping_mul(rv)),
+        BinaryOp::Div => if rv == 0 { None } else { Some(lv.wrapping_div(rv)) },
+        BinaryOp::Mod => if rv == 0 { None } else { Some(lv.wrapping_rem(rv)) },
+        BinaryOp::Add => Some(lv.wrapping_add(rv)),
+        BinaryOp::Sub => Some(lv.wrapping_sub(rv)),
+        // the count is taken modulo 32, as the target does
+        BinaryOp::Shl => Some(lv.wrapping_shl(rv as u32)),
+        BinaryOp::Shr => Some(lv.wrapping_shr(rv as u32)),
+        BinaryOp::Lt => Some((lv < rv).into()),
+        BinaryOp::Gt => Some((lv > rv).into()),
+        BinaryOp::Le => Some((lv <= rv).into()),
+        BinaryOp::Ge => Some((lv >= rv).into()),
+        BinaryOp::Eq => Some((lv == rv).into()),
+        BinaryOp::Neq => Some((lv != rv).into()),
+        BinaryOp::BitAnd => Some(lv & rv),
+        BinaryOp::BitXor => Some(lv ^ rv),
+        BinaryOp::BitOr => Some(lv | rv),
+        BinaryOp::And | BinaryOp::Or => unreachable!(),
+      }
+    },
+  }
+}
+
+// value of a binary expression on float constants, as bits, or as an int for comparisons
+pub fn eval_float_binary(op: &BinaryOp, lv: Option<i32>, rv: Option<i32>) -> Option<i32> {
+  let (lv, rv) = (f32::from_bits(lv? as u32), f32::from_bits(rv? as u32));
+  let bits = |v: f32| Some(v.to_bits() as i32);
+  match op {
+    BinaryOp::Mul => bits(lv * rv),
+    BinaryOp::Div => bits(lv / rv),
+    BinaryOp::Add => bits(lv + rv),
+    BinaryOp::Sub => bits(lv - rv),
+    BinaryOp::Lt => Some((lv < rv).into()),
+    BinaryOp::Gt => Some((lv > rv).into()),
+    BinaryOp::Le => Some((lv <= rv).into()),
+    BinaryOp::Ge => Some((lv >= rv).into()),
+    BinaryOp::Eq => Some((lv == rv).into()),
+    BinaryOp::Neq => Some((lv != rv).into()),
+    _ => unreachable!(), // int only, checked by semantic analysis
+  }
+}
+
+// whether folding a binary expression wraps around, as the target does
+pub fn overflows(op: &BinaryOp, lv: i32, rv: i32) -> bool {
+  match op {
+    BinaryOp::Mul => lv.checked_mul(rv).is_none(),
+    BinaryOp::Div => rv != 0 && lv.checked_div(rv).is_none(),
+    BinaryOp::Mod => rv != 0 && lv.checked_rem(rv).is_none(),
+    BinaryOp::Add => lv.checked_add(rv).is_none(),
+    BinaryOp::Sub => lv.checked_sub(rv).is_none(),
+    _ => false,
   }
-}
\ No newline at end of file
+}
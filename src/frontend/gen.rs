@@ -1,296 +1,313 @@
 use super::ast::*;
-use super::config::{
-  Value as SymValue,
-  Config,
-};
+use super::config::Config;
 use super::value::Value;
 use super::value::Initializer;
-use super::FrontendError;
+use super::expr::{convert, negated_int_literal};
 
 use koopa::ir::builder_traits::*;
 use koopa::ir::{
   Type,
-  TypeKind,
-  // Value as IrValue,
+  Value as IrValue,
   BinaryOp as IrBinaryOp,
 };
 
-pub type Result<T> = std::result::Result<T, FrontendError>;
-
-pub trait ProgramGen<'ast> {
+// generation from a checked ast, so nothing can fail here
+pub trait ProgramGen {
   type Out;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out>;
+  fn generate(&self, config: &mut Config) -> Self::Out;
+}
+
+// ir type of a symbol, floats are carried as the bits of an i32
+fn ir_type(ty: &Ty) -> Type {
+  match ty {
+    Ty::Void => Type::get_unit(),
+    Ty::Int | Ty::Float => Type::get_i32(),
+    Ty::Array(_, dims) => dims.iter().rev().fold(Type::get_i32(), |ty, len| Type::get_array(ty, *len)),
+    Ty::Ptr(btype, dims) => Type::get_pointer(ir_type(&Ty::Array(*btype, dims.clone()))),
+    Ty::Str => Type::get_pointer(Type::get_i32()),
+    Ty::Unknown | Ty::Func(..) => unreachable!(),
+  }
+}
+
+// type of the scalars in a var, the var itself or its elements
+fn scalar_ty(ty: &Ty) -> Ty {
+  match ty {
+    Ty::Array(btype, _) | Ty::Ptr(btype, _) => (*btype).into(),
+    ty => ty.clone(),
+  }
+}
+
+// type both operands are converted to, float if either one is
+fn common_ty(lhs: &Ty, rhs: &Ty) -> Ty {
+  if *lhs == Ty::Float || *rhs == Ty::Float { Ty::Float } else { Ty::Int }
+}
+
+// a value of type [from] as one of type [to]
+fn convert_value(value: IrValue, from: &Ty, to: &Ty, config: &mut Config) -> IrValue {
+  match (from, to) {
+    (Ty::Int, Ty::Float) => config.float_op("__itof", vec![value]),
+    (Ty::Float, Ty::Int) => config.float_op("__ftoi", vec![value]),
+    _ => value,
+  }
+}
+
+// a binary operation on operands of type [ty], floats through the intrinsics
+fn binary(op: &BinaryOp, lhs: IrValue, rhs: IrValue, ty: &Ty, config: &mut Config) -> IrValue {
+  if *ty == Ty::Float {
+    let name = match op {
+      BinaryOp::Add => "__fadd",
+      BinaryOp::Sub => "__fsub",
+      BinaryOp::Mul => "__fmul",
+      BinaryOp::Div => "__fdiv",
+      BinaryOp::Eq => "__feq",
+      BinaryOp::Neq => "__fne",
+      BinaryOp::Lt => "__flt",
+      BinaryOp::Le => "__fle",
+      BinaryOp::Gt => "__fgt",
+      BinaryOp::Ge => "__fge",
+      _ => unreachable!(), // int only, checked by semantic analysis
+    };
+    config.float_op(name, vec![lhs, rhs])
+  } else {
+    let op = op.generate(config);
+    let binary = config.new_value_builder().binary(op, lhs, rhs);
+    config.insert_instr(binary);
+    binary
+  }
 }
 
-impl<'ast> ProgramGen<'ast> for CompUnit {
+impl ProgramGen for CompUnit {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     // generate decl for lib_functions
-    config.new_decl("getint", vec![], Type::get_i32())?;
-    config.new_decl("getch", vec![], Type::get_i32())?;
-    config.new_decl(
-      "getarray", 
-      vec![Type::get_pointer(Type::get_i32())], 
-      Type::get_i32()
-    )?;
-    config.new_decl("putint", vec![Type::get_i32()], Type::get_unit())?;
-    config.new_decl("putch", vec![Type::get_i32()], Type::get_unit())?;
-    config.new_decl(
-      "putarray", 
-      vec![
-        Type::get_i32(),
-        Type::get_pointer(Type::get_i32())
-      ], 
-      Type::get_unit()
-    )?;
-    config.new_decl("starttime", vec![], Type::get_unit())?;
-    config.new_decl("stoptime", vec![], Type::get_unit())?;
-
-    for func in &self.global_def {
-      match func {
-        GlobalDef::Decl(decl) => decl.generate(config)?,
-        GlobalDef::FuncDef(funcdef) => funcdef.generate(config)?,
+    for (i, symbol) in config.symbols().iter().enumerate() {
+      if let (Ty::Func(ret_ty, params, _), None) = (&symbol.ty, symbol.span) {
+        let params = params.iter().map(ir_type).collect();
+        config.new_decl(SymbolId(i), &symbol.ident, params, ir_type(ret_ty));
+      }
+    }
+
+    // signatures and globals first, a function may use the ones defined after it
+    for def in &self.global_def {
+      if let GlobalDef::FuncDef(funcdef) = def {
+        funcdef.declare(config);
+      }
+    }
+    let decls: Vec<_> = self.global_def.iter().filter_map(|def| match def {
+      GlobalDef::Decl(decl) => Some(decl),
+      GlobalDef::FuncDef(_) => None,
+    }).collect();
+    decls.iter().for_each(|decl| decl.generate(config));
+    for decl in decls {
+      if let Decl::VarDecl(VarDecl { item, is_extern: true, .. }) = decl {
+        item.iter().filter(|def| def.initial.is_none()).for_each(|def| def.declare(config));
+      }
+    }
+
+    for def in &self.global_def {
+      if let GlobalDef::FuncDef(funcdef) = def {
+        funcdef.generate(config);
       }
     }
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for FuncDef {
+impl FuncDef {
+  fn declare(&self, config: &mut Config) {
+    let id = self.symbol.unwrap();
+    let (ret_ty, params_ty) = match &config.symbol(id).ty {
+      Ty::Func(ret_ty, params, _) => (ir_type(ret_ty), params.iter().map(ir_type).collect()),
+      _ => unreachable!(),
+    };
+    config.declare_func(id, &self.ident, params_ty, ret_ty);
+  }
+}
+
+impl ProgramGen for FuncDef {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let func_ty = self.func_type.generate(config)?;
-    let params_ty = self.params.iter().map(|p|
-      p.generate(config)
-    ).collect::<Result<Vec<_>>>()?;
-
-    config.enter_func(
-      &self.ident,
-      params_ty,
-      func_ty,
-    )?;
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    // a prototype is declared along with the other signatures
+    let Some(block) = &self.block else {
+      return;
+    };
+    config.enter_func(self.symbol.unwrap());
 
     // generate symbol for function args
     let p_params = config.func_mut().params().to_owned();
     for (p, v) in self.params.iter().zip(p_params) {
       let ty = config.value_ty(v);
-      let alloc = config.new_value_builder().alloc(ty);
-      config.insert_instr(alloc);
+      let alloc = config.new_alloc(ty);
       config.set_name(alloc, &p.ident);
       let store = config.new_value_builder().store(v, alloc);
       config.insert_instr(store);
-      config.new_value(&p.ident, SymValue::Value(alloc))?;
+      config.new_value(p.symbol.unwrap(), alloc);
     }
 
-    self.block.generate(config)?;
+    block.generate(config);
 
     config.leave_func();
-    Ok(())
-  }
-}
-
-impl<'ast> ProgramGen<'ast> for FuncType {
-  type Out = Type;
-  fn generate(&'ast self, _config: &mut Config<'ast>) -> Result<Self::Out> {
-    match self {
-      Self::Int => Ok(Type::get_i32()),
-      Self::Void => Ok(Type::get_unit()),
-    }
   }
 }
 
-impl<'ast> ProgramGen<'ast> for FuncFParam {
-  type Out = Type;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    if let Some(dims) = &self.dims { // array args
-      Ok(Type::get_pointer(dims.generate(config)?))
-    } else {
-      Ok(Type::get_i32())
-    }
-  }
-}
-
-impl<'ast> ProgramGen<'ast> for Block {
+impl ProgramGen for Block {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     for blockitem in &self.item {
-      blockitem.generate(config)?;
+      blockitem.generate(config);
     }
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for BlockItem {
+impl ProgramGen for BlockItem {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     match self {
-      Self::Decl(decl) => decl.generate(config)?,
-      Self::Stmt(stmt) => stmt.generate(config)?,
+      Self::Decl(decl) => decl.generate(config),
+      Self::Stmt(stmt) => stmt.generate(config),
     }
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for Decl {
+impl ProgramGen for Decl {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     match self {
-      Self::ConstDecl(c) => c.generate(config),
-      Self::VarDecl(v) => v.generate(config),
+      Self::ConstDecl(c) => c.item.iter().for_each(|def| def.generate(config)),
+      // `extern` items without initializer are declared once the definitions are known
+      Self::VarDecl(v) => v.item.iter()
+        .filter(|def| !v.is_extern || def.initial.is_some())
+        .for_each(|def| def.generate(config)),
     }
   }
 }
 
-impl<'ast> ProgramGen<'ast> for ConstDecl {
+impl ProgramGen for ConstDef {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    for constdef in &self.item {
-      constdef.generate(config)?;
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    // scalar consts are inlined at their uses
+    if self.dims.is_empty() {
+      return;
     }
-    Ok(())
-  }
-}
 
-impl<'ast> ProgramGen<'ast> for ConstDef {
-  type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let ty = self.dims.generate(config)?;
-    let init = self.initial.generate(config)?.fit(&ty)?;
-
-    if ty.is_i32() { // create new imm number
-      match init {
-        Initializer::Const(i) => {
-          config.new_value(&self.ident, SymValue::Const(i))?
-        },
-        _ => unreachable!(),
-      }
+    let dims = self.dims.generate(config);
+    let symbol_ty = &config.symbol(self.symbol.unwrap()).ty;
+    let init = self.initial.initializer(&scalar_ty(symbol_ty)).fit(&dims).unwrap();
+    let ty = ir_type(symbol_ty);
+    let value = if config.is_global() {
+      let init = init.as_const(config);
+      config.global_new_value_builder().global_alloc(init)
     } else {
-      let value = if config.is_global() {
-        let init = init.as_const(config)?;
-        let value = config.global_new_value_builder().global_alloc(init);
-        value
-      } else {
-        let value = config.new_value_builder().alloc(ty);
-        config.insert_instr(value);
-        init.as_store(config, value);
-        value
-      };
-      config.set_name(value, &self.ident);
-      config.new_value(&self.ident, SymValue::Value(value))?;
-    }
-    Ok(())
+      let value = config.new_alloc(ty);
+      init.as_store(config, value);
+      value
+    };
+    config.set_name(value, &self.ident);
+    config.new_value(self.symbol.unwrap(), value);
   }
 }
 
-impl<'ast> ProgramGen<'ast> for ConstInitVal {
-  type Out = Initializer;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    Ok(match self {
-      Self::Exp(constexp) => Initializer::Const(constexp.generate(config)?),
+impl ConstInitVal {
+  // with values converted to scalars of type [ty]
+  fn initializer(&self, ty: &Ty) -> Initializer {
+    match self {
+      Self::Exp(constexp) => Initializer::Const(convert(constexp.exp.value.unwrap(), &constexp.exp.ty, ty)),
       Self::List(list) => Initializer::List(
-        list.iter().map(|i| i.generate(config)).collect::<Result<_>>()?
+        list.iter().map(|i| i.initializer(ty)).collect()
       ),
-    })
-  }
-}
-
-impl<'ast> ProgramGen<'ast> for VarDecl {
-  type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    for vardef in &self.item {
-      vardef.generate(config)?;
     }
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for VarDef {
+impl ProgramGen for VarDef {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let ty = self.dims.generate(config)?;
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    let dims = self.dims.generate(config);
+    let symbol_ty = &config.symbol(self.symbol.unwrap()).ty;
+    let ty = ir_type(symbol_ty);
     let alloc = match &self.initial {
       None => {
         if config.is_global() {
           let init = config.global_new_value_builder().zero_init(ty);
           config.global_new_value_builder().global_alloc(init)
         } else {
-          let alloc = config.new_value_builder().alloc(ty);
-          config.insert_instr(alloc);
-          alloc
+          config.new_alloc(ty)
         }
       },
       Some(init) => {
-        let init = init.generate(config)?.fit(&ty)?;
+        let init = init.initializer(&scalar_ty(symbol_ty), config).fit(&dims).unwrap();
 
         if config.is_global() {
-          let init = init.as_const(config)?;
+          let init = init.as_const(config);
           config.global_new_value_builder().global_alloc(init)
         } else {
-          let alloc = config.new_value_builder().alloc(ty);
-          config.insert_instr(alloc);
+          let alloc = config.new_alloc(ty);
           init.as_store(config, alloc);
           alloc
         }
       }
     };
     config.set_name(alloc, &self.ident);
-    config.new_value(&self.ident, SymValue::Value(alloc))
+    config.new_value(self.symbol.unwrap(), alloc);
+  }
+}
+
+impl VarDef {
+  // a global defined in another file if not in this one, it takes no storage here
+  fn declare(&self, config: &mut Config) {
+    let id = self.symbol.unwrap();
+    if config.vardef.contains_key(&id) {
+      return;
+    }
+    let ty = ir_type(&config.symbol(id).ty);
+    let init = config.global_new_value_builder().undef(ty);
+    let alloc = config.global_new_value_builder().global_alloc(init);
+    config.set_name(alloc, &self.ident);
+    config.new_value(id, alloc);
   }
 }
 
-impl<'ast> ProgramGen<'ast> for InitVal {
-  type Out = Initializer;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let init = match self {
+impl InitVal {
+  // with values converted to scalars of type [ty]
+  fn initializer(&self, ty: &Ty, config: &mut Config) -> Initializer {
+    match self {
       Self::Exp(exp) => {
         if config.is_global() {
-          Initializer::Const(exp.eval(config).ok_or(FrontendError::EvalConstExpFail)?)
+          Initializer::Const(convert(exp.value.unwrap(), &exp.ty, ty))
         } else {
-          Initializer::Value(exp.generate(config)?.as_int(config)?)
+          Initializer::Value(exp.generate_as(ty, config))
         }
       }
       Self::List(list) => Initializer::List( 
-        list.iter().map(|v| v.generate(config)).collect::<Result<Vec<_>>>()?
+        list.iter().map(|v| v.initializer(ty, config)).collect()
       ),
-    };
-    Ok(init)
+    }
   }
 }
 
-// get array type
-impl<'ast> ProgramGen<'ast> for Vec<ConstExp> {
-  type Out = Type;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let mut ty = Type::get_i32();
-    for exp in self.iter().rev() {
-      let len = exp.generate(config)?;
-      if len < 1 {
-        return Err(FrontendError::InvalidInitializer);
-      }
-      ty = Type::get_array(ty, len as usize);
-    }
-    Ok(ty)
+// get array dims
+impl ProgramGen for Vec<ConstExp> {
+  type Out = Vec<usize>;
+  fn generate(&self, _config: &mut Config) -> Self::Out {
+    self.iter().map(|exp| exp.exp.value.unwrap() as usize).collect()
   }
 }
 
-impl<'ast> ProgramGen<'ast> for Stmt {
+impl ProgramGen for Stmt {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    match self {
-      Self::Assign(assign) => assign.generate(config)?,
-      Self::ExpStmt(expstmt) => expstmt.generate(config)?,
-      Self::Block(block) => {
-        config.scope_in();
-        block.generate(config)?;
-        config.scope_out();
-      },
-      Self::If(if_stmt) => if_stmt.generate(config)?,
-      Self::While(while_stmt) => while_stmt.generate(config)?,
-      Self::Break(break_stmt) => break_stmt.generate(config)?,
-      Self::Continue(continue_stmt) => continue_stmt.generate(config)?,
-      Self::Return(exp) => {
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    match &self.kind {
+      StmtKind::ExpStmt(expstmt) => expstmt.generate(config),
+      StmtKind::Block(block) => block.generate(config),
+      StmtKind::If(if_stmt) => if_stmt.generate(config),
+      StmtKind::While(while_stmt) => while_stmt.generate(config),
+      StmtKind::For(for_stmt) => for_stmt.generate(config),
+      StmtKind::DoWhile(do_while) => do_while.generate(config),
+      StmtKind::Switch(switch) => switch.generate(config),
+      StmtKind::Break(break_stmt) => break_stmt.generate(config),
+      StmtKind::Continue(continue_stmt) => continue_stmt.generate(config),
+      StmtKind::Return(exp) => {
         if let Some(exp) = exp {
-          let value = exp.generate(config)?.as_int(config)?;
+          let value = exp.generate_as(config.ret_ty(), config);
           let ret_val = config.ret_val().unwrap();
           let store = config.new_value_builder().store(value, ret_val);
           config.insert_instr(store);
@@ -302,35 +319,70 @@ impl<'ast> ProgramGen<'ast> for Stmt {
         config.set_bb(skipped);
       },
     }
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for Assign {
-  type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let lval = self.lval.generate(config)?.as_ptr()?;
-    let exp = self.exp.generate(config)?.as_val(config)?;
+// the lval is evaluated once, giving the stored value,
+// a compound one is done in float if either side is one, then converted back
+impl ProgramGen for Assign {
+  type Out = Value;
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    let ty = self.lval.scalar_ty(config);
+    let lval = self.lval.generate(config).as_ptr();
+    let exp = match &self.op {
+      None => self.exp.generate_as(&ty, config),
+      Some(op) => {
+        let op_ty = common_ty(&ty, &self.exp.ty);
+        let exp = self.exp.generate_as(&op_ty, config);
+        let load = config.new_value_builder().load(lval);
+        config.insert_instr(load);
+        let load = convert_value(load, &ty, &op_ty, config);
+        let exp = binary(op, load, exp, &op_ty, config);
+        convert_value(exp, &op_ty, &ty, config)
+      },
+    };
     let store = config.new_value_builder().store(exp, lval);
     config.insert_instr(store);
-    Ok(())
+    Value::Int(exp)
   }
 }
 
-impl<'ast> ProgramGen<'ast> for ExpStmt {
+// the value before or after the update, for postfix and prefix forms
+fn inc_dec(lval: &LVal, incdec: &IncDec, config: &mut Config) -> Value {
+  let (op, prefix) = match incdec {
+    IncDec::PreInc => (BinaryOp::Add, true),
+    IncDec::PreDec => (BinaryOp::Sub, true),
+    IncDec::PostInc => (BinaryOp::Add, false),
+    IncDec::PostDec => (BinaryOp::Sub, false),
+  };
+  let ty = lval.scalar_ty(config);
+  let lval = lval.generate(config).as_ptr();
+  let load = config.new_value_builder().load(lval);
+  config.insert_instr(load);
+  let one = match ty {
+    Ty::Float => 1f32.to_bits() as i32,
+    _ => 1,
+  };
+  let one = config.new_value_builder().integer(one);
+  let update = binary(&op, load, one, &ty, config);
+  let store = config.new_value_builder().store(update, lval);
+  config.insert_instr(store);
+  Value::Int(if prefix { update } else { load })
+}
+
+impl ProgramGen for ExpStmt {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     if let Some(e) = &self.exp {
-      e.generate(config)?;
+      e.generate(config);
     }
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for If {
+impl ProgramGen for If {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let cond = self.cond.generate(config)?.as_int(config)?;
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    let cond = self.cond.generate_cond(config);
     let then_block = config.new_bb("%then".into());
 
     let end_if;
@@ -341,7 +393,7 @@ impl<'ast> ProgramGen<'ast> for If {
       config.insert_instr(branch);
 
       config.set_bb(else_block);
-      else_stmt.generate(config)?;
+      else_stmt.generate(config);
       let jump = config.new_value_builder().jump(end_if);
       config.insert_instr(jump);
     } else {
@@ -351,18 +403,17 @@ impl<'ast> ProgramGen<'ast> for If {
     }
 
     config.set_bb(then_block);
-    self.then_stmt.generate(config)?;
+    self.then_stmt.generate(config);
     let jump = config.new_value_builder().jump(end_if);
     config.insert_instr(jump);
 
     config.set_bb(end_if);
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for While {
+impl ProgramGen for While {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     let bb_entry = config.new_bb("%loop_entry".into());
     let bb_body = config.new_bb("%loop_body".into());
     let bb_end = config.new_bb("%loop_end".into());
@@ -370,86 +421,268 @@ impl<'ast> ProgramGen<'ast> for While {
     config.insert_instr(jump);
 
     config.set_bb(bb_entry);
-    let cond = self.cond.generate(config)?.as_int(config)?;
+    let cond = self.cond.generate_cond(config);
     let branch = config.new_value_builder().branch(cond, bb_body, bb_end);
     config.insert_instr(branch);
 
-    config.while_in(bb_entry, bb_end);
+    config.loop_in(Some(bb_entry), bb_end);
+    config.set_bb(bb_body);
+    self.stmt.generate(config);
+    let jump = config.new_value_builder().jump(bb_entry);
+    config.insert_instr(jump);
+    config.loop_out();
+
+    config.set_bb(bb_end);
+  }
+}
+
+impl ProgramGen for For {
+  type Out = ();
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    if let Some(init) = &self.init {
+      init.generate(config);
+    }
+    let bb_entry = config.new_bb("%for_entry".into());
+    let bb_body = config.new_bb("%for_body".into());
+    let bb_step = config.new_bb("%for_step".into());
+    let bb_end = config.new_bb("%for_end".into());
+    let jump = config.new_value_builder().jump(bb_entry);
+    config.insert_instr(jump);
+
+    config.set_bb(bb_entry);
+    let next = match &self.cond {
+      Some(cond) => {
+        let cond = cond.generate_cond(config);
+        config.new_value_builder().branch(cond, bb_body, bb_end)
+      },
+      None => config.new_value_builder().jump(bb_body),
+    };
+    config.insert_instr(next);
+
+    config.loop_in(Some(bb_step), bb_end);
     config.set_bb(bb_body);
-    self.stmt.generate(config)?;
+    self.stmt.generate(config);
+    let jump = config.new_value_builder().jump(bb_step);
+    config.insert_instr(jump);
+    config.loop_out();
+
+    config.set_bb(bb_step);
+    if let Some(step) = &self.step {
+      step.generate(config);
+    }
     let jump = config.new_value_builder().jump(bb_entry);
     config.insert_instr(jump);
-    config.while_out();
 
     config.set_bb(bb_end);
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for Break {
+impl ProgramGen for DoWhile {
+  type Out = ();
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    let bb_body = config.new_bb("%do_body".into());
+    let bb_cond = config.new_bb("%do_cond".into());
+    let bb_end = config.new_bb("%do_end".into());
+    let jump = config.new_value_builder().jump(bb_body);
+    config.insert_instr(jump);
+
+    config.loop_in(Some(bb_cond), bb_end);
+    config.set_bb(bb_body);
+    self.stmt.generate(config);
+    let jump = config.new_value_builder().jump(bb_cond);
+    config.insert_instr(jump);
+    config.loop_out();
+
+    config.set_bb(bb_cond);
+    let cond = self.cond.generate_cond(config);
+    let branch = config.new_value_builder().branch(cond, bb_body, bb_end);
+    config.insert_instr(branch);
+
+    config.set_bb(bb_end);
+  }
+}
+
+impl ProgramGen for Switch {
+  type Out = ();
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    let cond = self.cond.generate(config).as_int(config);
+    let bb_cases: Vec<_> = self.cases.iter().map(|_| config.new_bb("%case".into())).collect();
+    let bb_end = config.new_bb("%switch_end".into());
+
+    // compare with each case value in turn, then go to default
+    let mut bb_default = bb_end;
+    for (case, &bb_case) in self.cases.iter().zip(&bb_cases) {
+      match &case.value {
+        Some(value) => {
+          let value = config.new_value_builder().integer(value.exp.value.unwrap());
+          let eq = config.new_value_builder().binary(IrBinaryOp::Eq, cond, value);
+          config.insert_instr(eq);
+          let bb_next = config.new_bb("%case_test".into());
+          let branch = config.new_value_builder().branch(eq, bb_case, bb_next);
+          config.insert_instr(branch);
+          config.set_bb(bb_next);
+        },
+        None => bb_default = bb_case,
+      }
+    }
+    let jump = config.new_value_builder().jump(bb_default);
+    config.insert_instr(jump);
+
+    // cases fall through to the next one
+    config.loop_in(None, bb_end);
+    for (i, case) in self.cases.iter().enumerate() {
+      config.set_bb(bb_cases[i]);
+      for item in &case.items {
+        item.generate(config);
+      }
+      let next = bb_cases.get(i + 1).copied().unwrap_or(bb_end);
+      let jump = config.new_value_builder().jump(next);
+      config.insert_instr(jump);
+    }
+    config.loop_out();
+
+    config.set_bb(bb_end);
+  }
+}
+
+impl ProgramGen for Break {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     let dest = config.break_bb();
     let jump = config.new_value_builder().jump(dest);
     config.insert_instr(jump);
     let skipped = config.new_bb("%skipped".into());
     config.set_bb(skipped);
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for Continue {
+impl ProgramGen for Continue {
   type Out = ();
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
+  fn generate(&self, config: &mut Config) -> Self::Out {
     let dest = config.continue_bb();
     let jump = config.new_value_builder().jump(dest);
     config.insert_instr(jump);
     let skipped = config.new_bb("%skipped".into());
     config.set_bb(skipped);
-    Ok(())
   }
 }
 
-impl<'ast> ProgramGen<'ast> for Exp {
+impl ProgramGen for Exp {
   type Out = Value;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    match self {
-      Self::Number(v) => Ok(Value::Int(
-        if config.is_global() {
-          config.global_new_value_builder().integer(*v)
-        } else {
-          config.new_value_builder().integer(*v)
-        }
-      )),
-      Self::LVal(lval) => lval.generate(config),
-      Self::UnaryExp(uop, exp) => {
-        let value = exp.generate(config)?.as_int(config)?;
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    match &self.kind {
+      // scalar consts are inlined
+      ExpKind::Number(_) | ExpKind::FloatNumber(_) | ExpKind::LVal(_) if self.value.is_some() => {
+        Value::Int(config.new_value_builder().integer(self.value.unwrap()))
+      },
+      // folded whole by semantic analysis, the literal alone may not fit
+      ExpKind::UnaryExp(UnaryOp::Neg, exp) if negated_int_literal(exp).is_some() => {
+        Value::Int(config.new_value_builder().integer(self.value.unwrap()))
+      },
+      // literals out of range are rejected by semantic analysis
+      ExpKind::Number(_) | ExpKind::FloatNumber(_) => unreachable!(),
+      ExpKind::LVal(lval) => lval.generate(config),
+      ExpKind::Str(s) => {
+        let string = config.new_string(s);
         let zero = config.new_value_builder().integer(0);
-        let binary = match uop {
-          UnaryOp::Neg => config.new_value_builder().binary(IrBinaryOp::Sub, zero, value),
-          UnaryOp::Not => config.new_value_builder().binary(IrBinaryOp::Eq, zero, value),
+        let ptr = config.new_value_builder().get_elem_ptr(string, zero);
+        config.insert_instr(ptr);
+        Value::APtr(ptr)
+      },
+      ExpKind::Assign(assign) => assign.generate(config),
+      ExpKind::IncDec(lval, incdec) => inc_dec(lval, incdec, config),
+      ExpKind::Comma(lhs, rhs) => {
+        lhs.generate(config);
+        rhs.generate(config)
+      },
+      // a known condition selects the branch now
+      ExpKind::Cond(cond, then_exp, else_exp) if cond.value.is_some() => {
+        let exp = if cond.truth().unwrap() { then_exp } else { else_exp };
+        match self.ty {
+          Ty::Void => exp.generate(config),
+          _ => Value::Int(exp.generate_as(&self.ty, config)),
+        }
+      },
+      ExpKind::Cond(cond, then_exp, else_exp) => {
+        let result = match self.ty {
+          Ty::Void => None,
+          _ => Some(config.new_alloc(Type::get_i32())),
+        };
+        let cond = cond.generate_cond(config);
+        let then_bb = config.new_bb("%then_val".into());
+        let else_bb = config.new_bb("%else_val".into());
+        let end_bb = config.new_bb("%cond_end".into());
+        let branch = config.new_value_builder().branch(cond, then_bb, else_bb);
+        config.insert_instr(branch);
+
+        for (bb, exp) in [(then_bb, then_exp), (else_bb, else_exp)] {
+          config.set_bb(bb);
+          match result {
+            Some(result) => {
+              let value = exp.generate_as(&self.ty, config);
+              let store = config.new_value_builder().store(value, result);
+              config.insert_instr(store);
+            },
+            None => { exp.generate(config); },
+          }
+          let jump = config.new_value_builder().jump(end_bb);
+          config.insert_instr(jump);
+        }
+
+        config.set_bb(end_bb);
+        match result {
+          Some(result) => {
+            let load = config.new_value_builder().load(result);
+            config.insert_instr(load);
+            Value::Int(load)
+          },
+          None => Value::Nav,
+        }
+      },
+      ExpKind::UnaryExp(uop, exp) => {
+        let value = match uop {
+          UnaryOp::Not => exp.generate_cond(config),
+          _ => exp.generate(config).as_int(config),
+        };
+        let binary = match (uop, &exp.ty) {
+          // flipping the sign bit
+          (UnaryOp::Neg, Ty::Float) => {
+            let sign = config.new_value_builder().integer(i32::MIN);
+            config.new_value_builder().binary(IrBinaryOp::Xor, value, sign)
+          },
+          (UnaryOp::Neg, _) => {
+            let zero = config.new_value_builder().integer(0);
+            config.new_value_builder().binary(IrBinaryOp::Sub, zero, value)
+          },
+          (UnaryOp::Not, _) => {
+            let zero = config.new_value_builder().integer(0);
+            config.new_value_builder().binary(IrBinaryOp::Eq, zero, value)
+          },
+          (UnaryOp::BitNot, _) => {
+            let ones = config.new_value_builder().integer(-1);
+            config.new_value_builder().binary(IrBinaryOp::Xor, value, ones)
+          },
         };
         config.insert_instr(binary);
-        Ok(Value::Int(binary))
+        Value::Int(binary)
       },
-      Self::BinaryExp(lhs, op, rhs) => {
+      ExpKind::BinaryExp(lhs, op, rhs) => {
         match op {
           BinaryOp::And => {
-            let result = config.new_value_builder().alloc(Type::get_i32());
-            config.insert_instr(result);
+            let result = config.new_alloc(Type::get_i32());
             {
               let zero = config.new_value_builder().integer(0);
               let store = config.new_value_builder().store(zero, result);
               config.insert_instr(store);
             }
-            let lval = lhs.generate(config)?.as_int(config)?;
+            let lval = lhs.generate_cond(config);
             let reval = config.new_bb("%reval".into());
             let short_path = config.new_bb("%short_path".into());
             let branch = config.new_value_builder().branch(lval, reval, short_path);
             config.insert_instr(branch);
 
             config.set_bb(reval);
-            let rval = rhs.generate(config)?.as_int(config)?;
+            let rval = rhs.generate_cond(config);
             let zero = config.new_value_builder().integer(0);
             let rval = config.new_value_builder().binary(IrBinaryOp::NotEq, zero, rval);
             config.insert_instr(rval);
@@ -462,24 +695,23 @@ impl<'ast> ProgramGen<'ast> for Exp {
             config.set_bb(short_path);
             let load = config.new_value_builder().load(result);
             config.insert_instr(load);
-            Ok(Value::Int(load))
+            Value::Int(load)
           },
           BinaryOp::Or => {
-            let result = config.new_value_builder().alloc(Type::get_i32());
-            config.insert_instr(result);
+            let result = config.new_alloc(Type::get_i32());
             {
               let one = config.new_value_builder().integer(1);
               let store = config.new_value_builder().store(one, result);
               config.insert_instr(store);
             }
-            let lval = lhs.generate(config)?.as_int(config)?;
+            let lval = lhs.generate_cond(config);
             let reval = config.new_bb("%reval".into());
             let short_path = config.new_bb("%short_path".into());
             let branch = config.new_value_builder().branch(lval, short_path, reval);
             config.insert_instr(branch);
 
             config.set_bb(reval);
-            let rval = rhs.generate(config)?.as_int(config)?;
+            let rval = rhs.generate_cond(config);
             let zero = config.new_value_builder().integer(0);
             let rval = config.new_value_builder().binary(IrBinaryOp::NotEq, zero, rval);
             config.insert_instr(rval);
@@ -492,76 +724,97 @@ impl<'ast> ProgramGen<'ast> for Exp {
             config.set_bb(short_path);
             let load = config.new_value_builder().load(result);
             config.insert_instr(load);
-            Ok(Value::Int(load))
+            Value::Int(load)
           },
           other => {
-            let lval = lhs.generate(config)?.as_int(config)?;
-            let rval = rhs.generate(config)?.as_int(config)?;
-            let bop = other.generate(config)?;
-            let binary = config.new_value_builder().binary(bop, lval, rval);
-            config.insert_instr(binary);
-            Ok(Value::Int(binary))
+            let ty = common_ty(&lhs.ty, &rhs.ty);
+            let lval = lhs.generate_as(&ty, config);
+            let rval = rhs.generate_as(&ty, config);
+            Value::Int(binary(other, lval, rval, &ty, config))
           }
         }
       },
-      Self::FuncCall(ident, params) => {
-        let func = config.get_func(ident)?;
-        let args = params.iter().map(|p| 
-          p.generate(config)?.as_val(config)
-        ).collect::<Result<Vec<_>>>()?;
+      ExpKind::FuncCall(call) => {
+        let id = call.symbol.unwrap();
+        let func = config.get_func(id);
+        let params = match &config.symbol(id).ty {
+          Ty::Func(_, params, _) => params,
+          _ => unreachable!(),
+        };
+        let mut args: Vec<_> = call.args.iter().zip(params).map(|(p, ty)| match ty {
+          Ty::Int | Ty::Float => p.generate_as(ty, config),
+          _ => p.generate(config).as_val(config),
+        }).collect();
+        // more args of a variadic function take a word each, but a float is promoted to double,
+        // taking two words from an even one, as the psABI puts it in an aligned register pair
+        for p in &call.args[params.len()..] {
+          let value = p.generate(config).as_int(config);
+          if p.ty == Ty::Float {
+            if args.len() % 2 == 1 {
+              args.push(config.new_value_builder().integer(0));
+            }
+            args.push(config.float_op("__dlo", vec![value]));
+            args.push(config.float_op("__dhi", vec![value]));
+          } else {
+            args.push(value);
+          }
+        }
         let call = config.new_value_builder().call(func, args);
         config.insert_instr(call);
         
         if config.is_void(func) {
-          Ok(Value::Nav)
+          Value::Nav
         } else {
-          Ok(Value::Int(call))
+          Value::Int(call)
         }
       }
     }
   }
 }
 
-impl<'ast> ProgramGen<'ast> for ConstExp {
-  type Out = i32;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    self.exp.eval(config).ok_or(FrontendError::EvalConstExpFail)
+impl Exp {
+  // the value converted to a scalar of type [ty], constants are converted now
+  fn generate_as(&self, ty: &Ty, config: &mut Config) -> IrValue {
+    match self.value {
+      _ if self.ty == *ty => self.generate(config).as_int(config),
+      Some(v) => config.new_value_builder().integer(convert(v, &self.ty, ty)),
+      None => {
+        let value = self.generate(config).as_int(config);
+        convert_value(value, &self.ty, ty, config)
+      },
+    }
+  }
+
+  // the value as a condition, a float is compared with zero
+  fn generate_cond(&self, config: &mut Config) -> IrValue {
+    let value = self.generate(config).as_int(config);
+    match self.ty {
+      Ty::Float => {
+        let zero = config.new_value_builder().integer(0);
+        config.float_op("__fne", vec![value, zero])
+      },
+      _ => value,
+    }
+  }
+}
+
+impl LVal {
+  // type of the scalar an lval refers to, when assigned to
+  fn scalar_ty(&self, config: &Config) -> Ty {
+    scalar_ty(&config.symbol(self.symbol.unwrap()).ty)
   }
 }
 
-impl<'ast> ProgramGen<'ast> for LVal {
+impl ProgramGen for LVal {
   type Out = Value;
-  fn generate(&'ast self, config: &mut Config<'ast>) -> Result<Self::Out> {
-    let mut value = match config.get_value(&self.ident)? {
-      SymValue::Value(v) => v,
-      SymValue::Const(v) => {
-        return if self.indices.is_empty() {
-          Ok(Value::Int(config.new_value_builder().integer(v)))
-        } else {
-          Err(FrontendError::InvalidValueType)
-        };
-      }
-    };
-
-    let mut arr_args = false;
-    let mut dims = match config.value_ty(value).kind() {
-      TypeKind::Pointer(base0) => {
-        let mut ty = base0;
-        let mut dims = 0;
-
-        loop {
-          ty = match ty.kind() {
-            TypeKind::Array(base1, _) => base1,
-            TypeKind::Pointer(base1) => {
-              arr_args = true;
-              base1
-            },
-            _ => break dims,
-          };
-          dims += 1;
-        }
-      },
-      _ => 0,
+  fn generate(&self, config: &mut Config) -> Self::Out {
+    let id = self.symbol.unwrap();
+    let mut value = config.get_value(id);
+    let (arr_args, dims) = match &config.symbol(id).ty {
+      Ty::Int | Ty::Float => (false, 0),
+      Ty::Array(_, dims) => (false, dims.len()),
+      Ty::Ptr(_, dims) => (true, dims.len() + 1),
+      _ => unreachable!(),
     };
 
     if arr_args {
@@ -570,12 +823,7 @@ impl<'ast> ProgramGen<'ast> for LVal {
     }
 
     for (i, idx) in self.indices.iter().enumerate() {
-      dims -= 1;
-      if dims < 0 {
-        return Err(FrontendError::InvalidValueType);
-      }
-
-      let idx = idx.generate(config)?.as_int(config)?;
+      let idx = idx.generate(config).as_int(config);
       value = if arr_args && i == 0 {
         config.new_value_builder().get_ptr(value, idx)
       } else {
@@ -584,36 +832,41 @@ impl<'ast> ProgramGen<'ast> for LVal {
       config.insert_instr(value);
     }
 
-    if dims == 0 {
-      Ok(Value::Ptr(value))
+    if self.indices.len() == dims {
+      Value::Ptr(value)
     } else {
       if !arr_args || !self.indices.is_empty() {
         let zero = config.new_value_builder().integer(0);
         value = config.new_value_builder().get_elem_ptr(value, zero);
         config.insert_instr(value);
       }
-      Ok(Value::APtr(value))
+      Value::APtr(value)
     }
   }
 }
 
-impl<'ast> ProgramGen<'ast> for BinaryOp {
+impl ProgramGen for BinaryOp {
   type Out = IrBinaryOp;
-  fn generate(&'ast self, _config: &mut Config<'ast>) -> Result<Self::Out> {
-    Ok(match self {
+  fn generate(&self, _config: &mut Config) -> Self::Out {
+    match self {
       Self::Mul => IrBinaryOp::Mul,
       Self::Div => IrBinaryOp::Div,
       Self::Mod => IrBinaryOp::Mod,
       Self::Add => IrBinaryOp::Add,
       Self::Sub => IrBinaryOp::Sub,
+      Self::Shl => IrBinaryOp::Shl,
+      Self::Shr => IrBinaryOp::Sar,
       Self::Lt => IrBinaryOp::Lt,
       Self::Le => IrBinaryOp::Le,
       Self::Gt => IrBinaryOp::Gt,
       Self::Ge => IrBinaryOp::Ge,
       Self::Eq => IrBinaryOp::Eq,
       Self::Neq => IrBinaryOp::NotEq,
+      Self::BitAnd => IrBinaryOp::And,
+      Self::BitXor => IrBinaryOp::Xor,
+      Self::BitOr => IrBinaryOp::Or,
       Self::And => IrBinaryOp::And,
       Self::Or => IrBinaryOp::Or,
-    })
+    }
   }
 }
\ No newline at end of file
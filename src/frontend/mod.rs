@@ -1,19 +1,21 @@
 /*
   frontend of the compiler:
   - ast: definition of ast nodes
-  - gen: generation for koopa ir
-  - expr: evaluation of const expression
+  - sema: semantic analysis, giving a checked ast
+    - symbol ids and types
+    - error sink, collecting errors until the error limit
+  - gen: generation for koopa ir from the checked ast
+  - expr: evaluation of const expression, on ints and floats
+  - syntax: readable messages for syntax errors
   - config: maintainance of context information
     - program
     - function
-    - symbol table
-      - current (local) var table
-      - global func table 
-    - while loop entries and exits
+    - values of symbols
+    - break and continue targets of loops and switches
   - value: 
     - deal different value types
       - not a value (void)
-      - integer value (int a)
+      - integer value (int a), floats are carried as their bits
       - ptr value (int *a)
       - array ptr (int *a[])
     - implementation of initializers
@@ -24,12 +26,18 @@ mod gen;
 mod expr;
 mod value;
 mod config;
+mod syntax;
+mod sema;
 
 use gen::ProgramGen;
 use config::Config;
+use syntax::syntax_error;
+use sema::{Check, Sema, Symbol};
+use ast::CompUnit;
 
 use std::fmt;
-use koopa::ir::Program;
+use std::collections::{HashMap, HashSet};
+use koopa::ir::{Function, Program, Value};
 
 use lalrpop_util::lalrpop_mod;
 
@@ -38,35 +46,235 @@ lalrpop_mod! {
   sysy
 }
 
-pub fn generate_ir(input: String) -> Result<Program, FrontendError> {
-  let ast = sysy::CompUnitParser::new().parse(&input).map_err(
-    |e| FrontendError::ParseFailure(e.to_string())
-  )?;
+pub use ast::{Span, Ty};
+
+// checked ast, with its symbol table
+pub struct Checked {
+  pub ast: CompUnit,
+  pub symbols: Vec<Symbol>,
+}
+
+// parse and check a program, without generating ir
+pub fn check(
+  input: &str,
+  error_limit: usize,
+  warnings: &mut Vec<FrontendWarning>,
+) -> Result<Checked, Vec<FrontendError>> {
+  // syntax errors are all reported, before any semantic check
+  let mut recovered = Vec::new();
+  let ast = sysy::CompUnitParser::new().parse(&mut recovered, input);
+  let mut errors: Vec<_> = recovered.into_iter().map(|r| syntax_error(r.error, input)).collect();
+  let mut ast = match ast {
+    Ok(ast) if errors.is_empty() => ast,
+    Ok(_) => return Err(errors),
+    Err(e) => {
+      errors.push(syntax_error(e, input));
+      return Err(errors);
+    },
+  };
+
+  let mut sema = Sema::new(error_limit);
+  let result = ast.check(&mut sema);
+  let (symbols, mut errors, mut sema_warnings) = sema.finish();
+  warnings.append(&mut sema_warnings);
+  if let Err(e) = result {
+    errors.push(e);
+  }
+  if errors.is_empty() {
+    Ok(Checked { ast, symbols })
+  } else {
+    Err(errors)
+  }
+}
+
+// which params of a function are floats, and whether its result is
+pub type FloatSig = (Vec<bool>, bool);
+
+// generated ir, with the globals holding string literals, never written,
+// and the float signatures of functions
+pub struct Ir {
+  pub program: Program,
+  pub strings: HashSet<Value>,
+  pub floats: HashMap<Function, FloatSig>,
+}
+
+pub fn generate_ir(
+  input: &str,
+  error_limit: usize,
+  warnings: &mut Vec<FrontendWarning>,
+) -> Result<Ir, Vec<FrontendError>> {
+  let checked = check(input, error_limit, warnings)?;
   let mut program = Program::new();
-  ast.generate(&mut Config::new(&mut program))?;
-  Ok(program)
+  let mut config = Config::new(&mut program, &checked.symbols);
+  checked.ast.generate(&mut config);
+  let strings = config.strings.into_values().collect();
+  let floats = config.floats;
+  Ok(Ir { program, strings, floats })
 }
 
 // Deal errors that may occur in frontend
 
 pub enum FrontendError {
-  ParseFailure(String), // Error message reported by parser
-  UndeclaredId(String),
-  EvalConstExpFail,
-  MultiDef(String),
-  InvalidInitializer,
-  InvalidValueType,
+  ParseFailure(String, Span), // readable message of a syntax error
+  UndeclaredId(String, Span),
+  EvalConstExpFail(Span),
+  MultiDef(String, Span, Option<Span>), // with the previous definition, if not from library
+  ReservedIdent(String, Span), // global name taken by the compiler
+  ConflictingTypes(String, Ty, Span, Option<Span>), // with the type and span of the previous declaration, if not from library
+  InvalidInitializer(Span),
+  InvalidValueType(Span),
+  ArgCountMismatch(String, usize, usize, Span, Option<Span>), // expected, found, with the callee if not from library
+  ArgTypeMismatch(Ty, Ty, Span), // expected, found
+  VoidValueUsed(Span),
+  ReturnValueInVoid(String, Span),
+  MissingReturnValue(String, Span),
+  BreakOutsideLoop(Span),
+  ContinueOutsideLoop(Span),
+  DivisionByZero(Span), // in a constant expression
+  InvalidArrayDim(Span),
+  ArrayTooLarge(Span),
+  LiteralTooLarge(Span),
+  DuplicateCase(Option<i32>, Span, Span), // value or [None] for default, with the previous label
+  TooManyErrors, // error limit reached, generation stopped
+}
+
+impl FrontendError {
+  pub fn span(&self) -> Option<Span> {
+    match self {
+      Self::ParseFailure(_, span) => Some(*span),
+      Self::UndeclaredId(_, span) => Some(*span),
+      Self::EvalConstExpFail(span) => Some(*span),
+      Self::MultiDef(_, span, _) => Some(*span),
+      Self::ReservedIdent(_, span) => Some(*span),
+      Self::ConflictingTypes(_, _, span, _) => Some(*span),
+      Self::InvalidInitializer(span) => Some(*span),
+      Self::InvalidValueType(span) => Some(*span),
+      Self::ArgCountMismatch(_, _, _, span, _) => Some(*span),
+      Self::ArgTypeMismatch(_, _, span) => Some(*span),
+      Self::VoidValueUsed(span) => Some(*span),
+      Self::ReturnValueInVoid(_, span) => Some(*span),
+      Self::MissingReturnValue(_, span) => Some(*span),
+      Self::BreakOutsideLoop(span) => Some(*span),
+      Self::ContinueOutsideLoop(span) => Some(*span),
+      Self::DivisionByZero(span) => Some(*span),
+      Self::InvalidArrayDim(span) => Some(*span),
+      Self::ArrayTooLarge(span) => Some(*span),
+      Self::LiteralTooLarge(span) => Some(*span),
+      Self::DuplicateCase(_, span, _) => Some(*span),
+      Self::TooManyErrors => None,
+    }
+  }
 }
 
 impl fmt::Display for FrontendError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Self::ParseFailure(err) => write!(f, "parse failure: {}", &err),
-      Self::UndeclaredId(ident) => write!(f, "ident `{}` is undeclared", &ident),
-      Self::EvalConstExpFail => write!(f, "failed in eval const expr"),
-      Self::MultiDef(ident) => write!(f, "ident `{}` defined multiple times", &ident),
-      Self::InvalidInitializer => write!(f, "invalid initializer detected"),
-      Self::InvalidValueType => write!(f, "invalid value type detected"),
+      Self::ParseFailure(err, _) => write!(f, "{}", &err),
+      Self::UndeclaredId(ident, _) => write!(f, "ident `{}` is undeclared", &ident),
+      Self::EvalConstExpFail(_) => write!(f, "failed in eval const expr"),
+      Self::MultiDef(ident, _, _) => write!(f, "ident `{}` defined multiple times", &ident),
+      Self::ReservedIdent(ident, _) => write!(f, "ident `{}` is reserved for the compiler", &ident),
+      Self::ConflictingTypes(ident, _, _, _) => write!(f, "conflicting types for `{}`", ident),
+      Self::InvalidInitializer(_) => write!(f, "invalid initializer detected"),
+      Self::InvalidValueType(_) => write!(f, "invalid value type detected"),
+      Self::ArgCountMismatch(ident, expected, found, _, _) => write!(
+        f, "function `{}` takes {} argument{} but {} {} supplied",
+        ident, expected, if *expected == 1 { "" } else { "s" }, found, if *found == 1 { "was" } else { "were" },
+      ),
+      Self::ArgTypeMismatch(expected, found, _) => {
+        write!(f, "mismatched argument type: expected `{}`, found `{}`", expected, found)
+      },
+      Self::VoidValueUsed(_) => write!(f, "void value used in an expression"),
+      Self::ReturnValueInVoid(ident, _) => {
+        write!(f, "void function `{}` should not return a value", ident)
+      },
+      Self::MissingReturnValue(ident, _) => {
+        write!(f, "non-void function `{}` should return a value", ident)
+      },
+      Self::BreakOutsideLoop(_) => write!(f, "`break` outside of a loop"),
+      Self::ContinueOutsideLoop(_) => write!(f, "`continue` outside of a loop"),
+      Self::DivisionByZero(_) => write!(f, "division by zero in a constant expression"),
+      Self::InvalidArrayDim(_) => write!(f, "array dimension must be positive"),
+      Self::ArrayTooLarge(_) => write!(f, "array is too large"),
+      Self::LiteralTooLarge(_) => write!(f, "integer literal is too large for `int`"),
+      Self::DuplicateCase(Some(v), _, _) => write!(f, "duplicate case value `{}`", v),
+      Self::DuplicateCase(None, _, _) => write!(f, "multiple default labels in one switch"),
+      Self::TooManyErrors => write!(f, "too many errors emitted, stopping now"),
+    }
+  }
+}
+
+// Deal warnings that may occur in frontend, compilation goes on
+
+pub enum FrontendWarning {
+  MissingReturn(String, Span), // non-void function reaching its end
+  UnusedVariable(String, Span),
+  UnusedResult(String, Span), // call of a non-void function as a statement
+  UnreachableCode(Span),
+  Shadow(String, Span, Span), // with the shadowed definition
+  ConstantCondition(Span),
+  DivisionByZero(Span), // outside constant expressions, left to run time
+  Overflow(Span), // folded with wrapping
+  ShiftCount(Span), // taken modulo 32
+}
+
+// names of warnings with whether they are on by default, for -W<name> and -Wno-<name>
+pub const WARNINGS: [(&str, bool); 9] = [
+  ("return-type", true),
+  ("unused-variable", true),
+  ("unused-result", false),
+  ("unreachable-code", true),
+  ("shadow", false),
+  ("constant-condition", false),
+  ("div-by-zero", true),
+  ("overflow", true),
+  ("shift-count", true),
+];
+
+impl FrontendWarning {
+  pub fn span(&self) -> Span {
+    match self {
+      Self::MissingReturn(_, span) => *span,
+      Self::UnusedVariable(_, span) => *span,
+      Self::UnusedResult(_, span) => *span,
+      Self::UnreachableCode(span) => *span,
+      Self::Shadow(_, span, _) => *span,
+      Self::ConstantCondition(span) => *span,
+      Self::DivisionByZero(span) => *span,
+      Self::Overflow(span) => *span,
+      Self::ShiftCount(span) => *span,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::MissingReturn(..) => "return-type",
+      Self::UnusedVariable(..) => "unused-variable",
+      Self::UnusedResult(..) => "unused-result",
+      Self::UnreachableCode(_) => "unreachable-code",
+      Self::Shadow(..) => "shadow",
+      Self::ConstantCondition(_) => "constant-condition",
+      Self::DivisionByZero(_) => "div-by-zero",
+      Self::Overflow(_) => "overflow",
+      Self::ShiftCount(_) => "shift-count",
+    }
+  }
+}
+
+impl fmt::Display for FrontendWarning {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::MissingReturn(ident, _) => {
+        write!(f, "non-void function `{}` does not return a value in all control paths", ident)
+      },
+      Self::UnusedVariable(ident, _) => write!(f, "unused variable `{}`", ident),
+      Self::UnusedResult(ident, _) => write!(f, "result of `{}` is unused", ident),
+      Self::UnreachableCode(_) => write!(f, "unreachable code"),
+      Self::Shadow(ident, _, _) => write!(f, "declaration of `{}` shadows an outer one", ident),
+      Self::ConstantCondition(_) => write!(f, "condition is always constant"),
+      Self::DivisionByZero(_) => write!(f, "division by zero"),
+      Self::Overflow(_) => write!(f, "integer overflow in expression, the result wraps around"),
+      Self::ShiftCount(_) => write!(f, "shift count is negative or not less than the width of `int`"),
     }
   }
 }
@@ -75,4 +283,4 @@ impl fmt::Debug for FrontendError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{}", self)
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1031 @@
+/*
+  semantic analysis, run on the ast before ir generation:
+  - resolve every identifier to a symbol id
+  - give every expression a type, and a value if it is constant
+  - check the rules, collecting errors until the error limit
+*/
+
+use super::ast::*;
+use super::{FrontendError, FrontendWarning};
+use super::value::Initializer;
+use super::expr::{eval_unary, eval_binary, eval_float_binary, overflows, convert, negated_int_literal};
+
+use std::collections::{HashMap, HashSet};
+
+pub type Result<T> = std::result::Result<T, FrontendError>;
+
+#[derive(Clone)]
+pub struct Symbol {
+  pub ident: String,
+  pub ty: Ty,
+  pub is_const: bool,
+  pub value: Option<i32>, // value of a scalar const, floats as their bits
+  pub span: Option<Span>, // definition, [None] for lib functions
+}
+
+// names of the float intrinsics, declared by [Sema::use_floats]
+const INTRINSICS: [&str; 14] = [
+  "__fadd", "__fsub", "__fmul", "__fdiv",
+  "__feq", "__fne", "__flt", "__fle", "__fgt", "__fge",
+  "__itof", "__ftoi", "__dlo", "__dhi",
+];
+
+pub struct Sema {
+  symbols: Vec<Symbol>,
+  vardef: Vec<HashMap<String, SymbolId>>, // scoped var defs, innermost last
+  funcdef: HashMap<String, SymbolId>,
+  function: Option<SymbolId>, // current function
+  loop_depth: usize, // number of loops around current statement
+  switch_depth: usize, // number of switches around current statement
+  const_exp: bool, // inside an expression that must be constant
+  btype: BType, // base type of the declaration being checked
+  is_extern: bool, // whether the declaration being checked is `extern`
+  defined: HashSet<SymbolId>, // symbols with a definition, others are only declared so far
+  floats: bool, // whether floats are used, the intrinsics are declared then
+  used: HashSet<SymbolId>, // vars referred to somewhere, params count as used
+  errors: Vec<FrontendError>, // errors reported so far
+  warnings: Vec<FrontendWarning>,
+  error_limit: usize, // stop after this many errors, [0] for no limit
+}
+
+pub trait Check {
+  type Out;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out>;
+}
+
+impl Sema {
+  pub fn new(error_limit: usize) -> Self {
+    let mut sema = Self {
+      symbols: Vec::new(),
+      vardef: vec![HashMap::new()],
+      funcdef: HashMap::new(),
+      function: None,
+      loop_depth: 0,
+      switch_depth: 0,
+      const_exp: false,
+      btype: BType::Int,
+      is_extern: false,
+      defined: HashSet::new(),
+      floats: false,
+      used: HashSet::new(),
+      errors: Vec::new(),
+      warnings: Vec::new(),
+      error_limit,
+    };
+
+    // lib functions
+    let int_ptr = Ty::Ptr(BType::Int, Vec::new());
+    let float_ptr = Ty::Ptr(BType::Float, Vec::new());
+    sema.new_lib_func("getint", vec![], Ty::Int);
+    sema.new_lib_func("getch", vec![], Ty::Int);
+    sema.new_lib_func("getfloat", vec![], Ty::Float);
+    sema.new_lib_func("getarray", vec![int_ptr.clone()], Ty::Int);
+    sema.new_lib_func("getfarray", vec![float_ptr.clone()], Ty::Int);
+    sema.new_lib_func("putint", vec![Ty::Int], Ty::Void);
+    sema.new_lib_func("putch", vec![Ty::Int], Ty::Void);
+    sema.new_lib_func("putfloat", vec![Ty::Float], Ty::Void);
+    sema.new_lib_func("putarray", vec![Ty::Int, int_ptr], Ty::Void);
+    sema.new_lib_func("putfarray", vec![Ty::Int, float_ptr], Ty::Void);
+    // the format is followed by ints and floats
+    sema.new_lib("putf", Ty::Func(Box::new(Ty::Void), vec![Ty::Str], true));
+    sema.new_lib_func("starttime", vec![], Ty::Void);
+    sema.new_lib_func("stoptime", vec![], Ty::Void);
+    sema
+  }
+
+  // symbol table, errors and warnings, after checking
+  pub fn finish(self) -> (Vec<Symbol>, Vec<FrontendError>, Vec<FrontendWarning>) {
+    (self.symbols, self.errors, self.warnings)
+  }
+
+  pub fn warn(&mut self, w: FrontendWarning) {
+    self.warnings.push(w);
+  }
+
+  // record an error and keep going, unless the error limit is reached
+  pub fn report(&mut self, e: FrontendError) -> Result<()> {
+    self.errors.push(e);
+    if self.error_limit != 0 && self.errors.len() >= self.error_limit {
+      Err(FrontendError::TooManyErrors)
+    } else {
+      Ok(())
+    }
+  }
+
+  pub fn symbol(&self, id: SymbolId) -> &Symbol {
+    &self.symbols[id.0]
+  }
+
+  // name and return type of current function
+  fn function(&self) -> (String, Ty) {
+    let symbol = self.symbol(self.function.unwrap());
+    match &symbol.ty {
+      Ty::Func(ret_ty, ..) => (symbol.ident.clone(), ret_ty.as_ref().clone()),
+      _ => unreachable!(),
+    }
+  }
+
+  fn is_global(&self) -> bool {
+    self.vardef.len() == 1
+  }
+
+  // global initializers are constant as well
+  fn in_const_exp(&self) -> bool {
+    self.const_exp || self.is_global()
+  }
+
+  fn new_symbol(&mut self, symbol: Symbol) -> SymbolId {
+    self.symbols.push(symbol);
+    SymbolId(self.symbols.len() - 1)
+  }
+
+  fn new_lib_func(&mut self, ident: &str, params: Vec<Ty>, ret_ty: Ty) {
+    self.new_lib(ident, Ty::Func(Box::new(ret_ty), params, false));
+  }
+
+  fn new_lib(&mut self, ident: &str, ty: Ty) {
+    let id = self.new_symbol(Symbol { ident: ident.into(), ty, is_const: false, value: None, span: None });
+    self.funcdef.insert(ident.into(), id);
+  }
+
+  // float operations are calls to intrinsics lowered by the backend, declared once floats are used,
+  // they cannot be called from the source, nor any global take their names
+  fn use_floats(&mut self) {
+    if std::mem::replace(&mut self.floats, true) {
+      return;
+    }
+    let (int, float) = (Ty::Int, Ty::Float);
+    for op in ["__fadd", "__fsub", "__fmul", "__fdiv"] {
+      self.new_intrinsic(op, vec![float.clone(), float.clone()], float.clone());
+    }
+    for op in ["__feq", "__fne", "__flt", "__fle", "__fgt", "__fge"] {
+      self.new_intrinsic(op, vec![float.clone(), float.clone()], int.clone());
+    }
+    self.new_intrinsic("__itof", vec![int.clone()], float.clone());
+    self.new_intrinsic("__ftoi", vec![float.clone()], int.clone());
+    // a float promoted to double for a variadic call, as two words
+    self.new_intrinsic("__dlo", vec![float.clone()], int.clone());
+    self.new_intrinsic("__dhi", vec![float], int);
+  }
+
+  fn check_reserved(&mut self, ident: &str, span: Span) -> Result<()> {
+    if INTRINSICS.contains(&ident) {
+      self.report(FrontendError::ReservedIdent(ident.into(), span))?;
+    }
+    Ok(())
+  }
+
+  fn new_intrinsic(&mut self, ident: &str, params: Vec<Ty>, ret_ty: Ty) {
+    let ty = Ty::Func(Box::new(ret_ty), params, false);
+    self.new_symbol(Symbol { ident: ident.into(), ty, is_const: false, value: None, span: None });
+  }
+
+  fn scope_in(&mut self) {
+    self.vardef.push(HashMap::new());
+  }
+
+  // leaving a scope, its vars can no longer be used
+  fn scope_out(&mut self) {
+    let scope = self.vardef.pop().unwrap();
+    let mut unused: Vec<_> = scope.into_iter().filter(|(_, id)| !self.used.contains(id)).collect();
+    unused.sort_by_key(|(_, id)| id.0);
+    for (ident, id) in unused {
+      self.warn(FrontendWarning::UnusedVariable(ident, self.symbol(id).span.unwrap()));
+    }
+  }
+
+  // define a var in current scope, a redefinition is reported but keeps the first one,
+  // a global one is only declared unless [defines]
+  fn new_var(&mut self, symbol: Symbol, defines: bool) -> Result<SymbolId> {
+    let (ident, span) = (symbol.ident.clone(), symbol.span.unwrap());
+    if self.is_global() {
+      self.check_reserved(&ident, span)?;
+    }
+    let prev = self.vardef.last().unwrap().get(&ident)
+      .or(if self.is_global() { self.funcdef.get(&ident) } else { None })
+      .copied();
+    if let Some(prev) = prev.filter(|_| self.is_global()) {
+      if let Some(id) = self.redeclare(prev, &symbol, defines)? {
+        return Ok(id);
+      }
+    }
+    let id = self.new_symbol(symbol);
+    match prev.map(|prev| self.symbol(prev).span) {
+      Some(prev) => self.report(FrontendError::MultiDef(ident, span, prev))?,
+      None => {
+        if let Some(outer) = self.get_var(&ident) {
+          self.warn(FrontendWarning::Shadow(ident.clone(), span, self.symbol(outer).span.unwrap()));
+        }
+        self.vardef.last_mut().unwrap().insert(ident, id);
+        if defines {
+          self.defined.insert(id);
+        }
+      },
+    }
+    Ok(id)
+  }
+
+  // a function, only declared unless [defines]
+  fn new_func(&mut self, symbol: Symbol, defines: bool) -> Result<SymbolId> {
+    let (ident, span) = (symbol.ident.clone(), symbol.span.unwrap());
+    self.check_reserved(&ident, span)?;
+    let prev = self.funcdef.get(&ident).or(self.vardef[0].get(&ident)).copied();
+    if let Some(prev) = prev {
+      if let Some(id) = self.redeclare(prev, &symbol, defines)? {
+        return Ok(id);
+      }
+    }
+    let id = self.new_symbol(symbol);
+    match prev.map(|prev| self.symbol(prev).span) {
+      Some(prev) => self.report(FrontendError::MultiDef(ident, span, prev))?,
+      None => {
+        self.funcdef.insert(ident, id);
+        if defines {
+          self.defined.insert(id);
+        }
+      },
+    }
+    Ok(id)
+  }
+
+  // a declaration of the global [prev] again, sharing its symbol, which then points at the definition,
+  // one of another type gets a symbol of its own, [None] if it is defined twice or is another kind of symbol,
+  // lib functions may only be declared again
+  fn redeclare(&mut self, prev: SymbolId, symbol: &Symbol, defines: bool) -> Result<Option<SymbolId>> {
+    let is_func = |ty: &Ty| matches!(ty, Ty::Func(..));
+    let declared = self.symbol(prev);
+    let same_kind = is_func(&declared.ty) == is_func(&symbol.ty) && declared.is_const == symbol.is_const;
+    let is_lib = declared.span.is_none();
+    if !same_kind || (defines && (is_lib || self.defined.contains(&prev))) {
+      return Ok(None);
+    }
+    if declared.ty != symbol.ty && declared.ty != Ty::Unknown && symbol.ty != Ty::Unknown {
+      let e = FrontendError::ConflictingTypes(symbol.ident.clone(), declared.ty.clone(), symbol.span.unwrap(), declared.span);
+      self.report(e)?;
+      return Ok(Some(self.new_symbol(symbol.clone())));
+    }
+    if defines {
+      self.defined.insert(prev);
+      self.symbols[prev.0].span = symbol.span;
+    }
+    Ok(Some(prev))
+  }
+
+  fn get_var(&self, ident: &str) -> Option<SymbolId> {
+    self.vardef.iter().rev().find_map(|scope| scope.get(ident)).copied()
+  }
+
+  fn get_func(&self, ident: &str) -> Option<SymbolId> {
+    self.funcdef.get(ident).copied()
+  }
+
+  // a condition, constant ones are suspicious unless written as a literal the [idiom] allows
+  fn check_cond(&mut self, cond: &mut Exp, idiom: fn(i64) -> bool) -> Result<()> {
+    cond.check(self)?;
+    self.expect_arith(cond)?;
+    let literal = matches!(cond.kind, ExpKind::Number(v) if idiom(v));
+    if cond.value.is_some() && !literal {
+      self.warn(FrontendWarning::ConstantCondition(cond.span));
+    }
+    Ok(())
+  }
+
+  // an expression used as an integer
+  fn expect_int(&mut self, exp: &Exp) -> Result<()> {
+    match exp.ty {
+      Ty::Int | Ty::Unknown => Ok(()),
+      Ty::Void => self.report(FrontendError::VoidValueUsed(exp.span)),
+      _ => self.report(FrontendError::InvalidValueType(exp.span)),
+    }
+  }
+
+  // an expression used as a number, int or float
+  fn expect_arith(&mut self, exp: &Exp) -> Result<()> {
+    match exp.ty {
+      Ty::Float => Ok(()),
+      _ => self.expect_int(exp),
+    }
+  }
+
+  // an expression converted to a scalar of type [ty], on initialization or return
+  fn expect_scalar(&mut self, exp: &Exp, ty: &Ty) -> Result<()> {
+    if *ty == Ty::Float {
+      self.use_floats();
+    }
+    self.expect_arith(exp)
+  }
+
+  // an operand of a binary operator, ints only for some of them
+  fn expect_operand(&mut self, op: &BinaryOp, exp: &Exp) -> Result<()> {
+    if int_only(op) { self.expect_int(exp) } else { self.expect_arith(exp) }
+  }
+
+  // value of a const expression used as an int, [None] if not constant or a float
+  fn int_const(&mut self, exp: &mut ConstExp) -> Result<Option<i32>> {
+    let value = exp.check(self)?;
+    if exp.exp.ty == Ty::Float {
+      self.report(FrontendError::InvalidValueType(exp.exp.span))?;
+      return Ok(None);
+    }
+    Ok(value)
+  }
+}
+
+impl Check for CompUnit {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    // signatures and globals first, so that a body can use the ones defined after it,
+    // an initializer only sees the globals declared before it
+    for def in &mut self.global_def {
+      match def {
+        GlobalDef::Decl(decl) => decl.check(sema)?,
+        GlobalDef::FuncDef(funcdef) => funcdef.check_signature(sema)?,
+      }
+    }
+    for def in &mut self.global_def {
+      if let GlobalDef::FuncDef(funcdef) = def {
+        funcdef.check(sema)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FuncDef {
+  fn check_signature(&mut self, sema: &mut Sema) -> Result<()> {
+    let ret_ty = match self.func_type {
+      FuncType::Int => Ty::Int,
+      FuncType::Float => Ty::Float,
+      FuncType::Void => Ty::Void,
+    };
+    let mut params_ty = Vec::new();
+    for param in &mut self.params {
+      params_ty.push(param.check(sema)?);
+    }
+
+    let ty = Ty::Func(Box::new(ret_ty), params_ty, false);
+    let symbol = Symbol { ident: self.ident.clone(), ty, is_const: false, value: None, span: Some(self.span) };
+    self.symbol = Some(sema.new_func(symbol, self.block.is_some())?);
+    Ok(())
+  }
+}
+
+// the body, after all signatures are declared
+impl Check for FuncDef {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    let Some(block) = &mut self.block else {
+      return Ok(());
+    };
+    let id = self.symbol.unwrap();
+    let (ret_ty, params_ty) = match &sema.symbol(id).ty {
+      Ty::Func(ret_ty, params, _) => (ret_ty.as_ref().clone(), params.clone()),
+      _ => unreachable!(),
+    };
+
+    // params share the scope with the function body
+    sema.scope_in();
+    sema.function = Some(id);
+    for (param, ty) in self.params.iter_mut().zip(params_ty) {
+      let symbol = Symbol { ident: param.ident.clone(), ty, is_const: false, value: None, span: Some(param.span) };
+      let id = sema.new_var(symbol, true)?;
+      sema.used.insert(id);
+      param.symbol = Some(id);
+    }
+    block.check(sema)?;
+    // main returns 0 when reaching its end
+    if ret_ty != Ty::Void && self.ident != "main" && block.falls_through() {
+      sema.warn(FrontendWarning::MissingReturn(self.ident.clone(), self.span));
+    }
+    sema.function = None;
+    sema.scope_out();
+    Ok(())
+  }
+}
+
+impl Check for FuncFParam {
+  type Out = Ty;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    match &mut self.dims {
+      Some(dims) => Ok(dims.check(sema)?.map_or(Ty::Unknown, |dims| Ty::Ptr(self.btype, dims))),
+      None => Ok(self.btype.into()),
+    }
+  }
+}
+
+impl Check for Block {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    self.item.check(sema)
+  }
+}
+
+// items of a block or of a case
+impl Check for Vec<BlockItem> {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    for item in self.iter_mut() {
+      item.check(sema)?;
+    }
+
+    // only the first statement that is never run is reported
+    let mut stmts = stmts(self).filter(|stmt| !matches!(stmt.kind, StmtKind::ExpStmt(ExpStmt { exp: None })));
+    if stmts.by_ref().any(|stmt| !stmt.falls_through()) {
+      if let Some(stmt) = stmts.next() {
+        sema.warn(FrontendWarning::UnreachableCode(stmt.span));
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Check for BlockItem {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    match self {
+      Self::Decl(decl) => decl.check(sema),
+      Self::Stmt(stmt) => stmt.check(sema),
+    }
+  }
+}
+
+impl Check for Decl {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    match self {
+      Self::ConstDecl(decl) => {
+        sema.btype = decl.btype;
+        decl.item.iter_mut().try_for_each(|def| def.check(sema))
+      },
+      Self::VarDecl(decl) => {
+        sema.btype = decl.btype;
+        sema.is_extern = decl.is_extern;
+        decl.item.iter_mut().try_for_each(|def| def.check(sema))
+      },
+    }
+  }
+}
+
+// array dims, [None] if some dim is invalid
+impl Check for Vec<ConstExp> {
+  type Out = Option<Vec<usize>>;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    let mut dims = Some(Vec::new());
+    let mut size = 4usize; // in bytes, offsets are 32-bit on the target
+    for exp in self.iter_mut() {
+      let len = match sema.int_const(exp)? {
+        Some(len) if len < 1 => {
+          sema.report(FrontendError::InvalidArrayDim(exp.exp.span))?;
+          None
+        },
+        Some(len) => {
+          size = size.saturating_mul(len as usize);
+          if size > i32::MAX as usize && dims.is_some() {
+            sema.report(FrontendError::ArrayTooLarge(exp.exp.span))?;
+            None
+          } else {
+            Some(len)
+          }
+        },
+        None => None,
+      };
+      dims = dims.zip(len).map(|(mut dims, len)| {
+        dims.push(len as usize);
+        dims
+      });
+    }
+    Ok(dims)
+  }
+}
+
+// value of a const expression, [None] if not constant
+impl Check for ConstExp {
+  type Out = Option<i32>;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    let outer = std::mem::replace(&mut sema.const_exp, true);
+    self.exp.check(sema)?;
+    sema.const_exp = outer;
+    sema.expect_arith(&self.exp)?;
+    if self.exp.value.is_none() && self.exp.ty != Ty::Unknown {
+      sema.report(FrontendError::EvalConstExpFail(self.exp.span))?;
+    }
+    Ok(self.exp.value)
+  }
+}
+
+impl Check for ConstDef {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    let dims = self.dims.check(sema)?;
+    let init = self.initial.check(sema)?;
+
+    let mut value = None;
+    if let Some(dims) = &dims {
+      match init.fit(dims) {
+        Some(Initializer::Const(v)) => value = Some(v),
+        Some(_) => {},
+        None => sema.report(FrontendError::InvalidInitializer(self.span))?,
+      }
+    }
+
+    let ty = dims.map_or(Ty::Unknown, |dims| Ty::decl(sema.btype, dims));
+    let symbol = Symbol { ident: self.ident.clone(), ty, is_const: true, value, span: Some(self.span) };
+    self.symbol = Some(sema.new_var(symbol, true)?);
+    Ok(())
+  }
+}
+
+// shape of the initializer, with values converted to the declared type
+impl Check for ConstInitVal {
+  type Out = Initializer;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    Ok(match self {
+      Self::Exp(exp) => {
+        let value = exp.check(sema)?;
+        Initializer::Const(value.map_or(0, |v| convert(v, &exp.exp.ty, &sema.btype.into())))
+      },
+      Self::List(list) => Initializer::List(
+        list.iter_mut().map(|i| i.check(sema)).collect::<Result<_>>()?
+      ),
+    })
+  }
+}
+
+impl Check for VarDef {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    let dims = self.dims.check(sema)?;
+    if let Some(initial) = &mut self.initial {
+      let init = initial.check(sema)?;
+      if dims.as_ref().is_some_and(|dims| init.fit(dims).is_none()) {
+        sema.report(FrontendError::InvalidInitializer(self.span))?;
+      }
+    }
+
+    let ty = dims.map_or(Ty::Unknown, |dims| Ty::decl(sema.btype, dims));
+    let symbol = Symbol { ident: self.ident.clone(), ty, is_const: false, value: None, span: Some(self.span) };
+    let defines = !sema.is_extern || self.initial.is_some();
+    self.symbol = Some(sema.new_var(symbol, defines)?);
+    Ok(())
+  }
+}
+
+// shape of the initializer, values are placeholders
+impl Check for InitVal {
+  type Out = Initializer;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    Ok(match self {
+      Self::Exp(exp) => {
+        let ty = sema.btype.into();
+        exp.check(sema)?;
+        sema.expect_scalar(exp, &ty)?;
+        // global vars are initialized statically
+        if sema.is_global() && exp.value.is_none() && exp.ty != Ty::Unknown {
+          sema.report(FrontendError::EvalConstExpFail(exp.span))?;
+        }
+        Initializer::Const(exp.value.map_or(0, |v| convert(v, &exp.ty, &ty)))
+      },
+      Self::List(list) => Initializer::List(
+        list.iter_mut().map(|i| i.check(sema)).collect::<Result<_>>()?
+      ),
+    })
+  }
+}
+
+impl Check for Stmt {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    match &mut self.kind {
+      StmtKind::ExpStmt(ExpStmt { exp: Some(exp) }) => {
+        exp.check(sema)?;
+        match &exp.kind {
+          ExpKind::FuncCall(call) if exp.ty != Ty::Void => {
+            sema.warn(FrontendWarning::UnusedResult(call.ident.clone(), exp.span));
+          },
+          _ => {},
+        }
+      },
+      StmtKind::ExpStmt(ExpStmt { exp: None }) => {},
+      StmtKind::Block(block) => {
+        sema.scope_in();
+        block.check(sema)?;
+        sema.scope_out();
+      },
+      StmtKind::If(if_stmt) => {
+        sema.check_cond(&mut if_stmt.cond, |_| false)?;
+        if_stmt.then_stmt.check(sema)?;
+        if let Some(else_stmt) = &mut if_stmt.else_stmt {
+          else_stmt.check(sema)?;
+        }
+      },
+      StmtKind::While(while_stmt) => {
+        // `while (1)` is the usual endless loop
+        sema.check_cond(&mut while_stmt.cond, |v| v != 0)?;
+        sema.loop_depth += 1;
+        while_stmt.stmt.check(sema)?;
+        sema.loop_depth -= 1;
+      },
+      StmtKind::For(for_stmt) => {
+        sema.scope_in();
+        if let Some(init) = &mut for_stmt.init {
+          init.check(sema)?;
+        }
+        if let Some(cond) = &mut for_stmt.cond {
+          sema.check_cond(cond, |v| v != 0)?;
+        }
+        if let Some(step) = &mut for_stmt.step {
+          step.check(sema)?;
+        }
+        sema.loop_depth += 1;
+        for_stmt.stmt.check(sema)?;
+        sema.loop_depth -= 1;
+        sema.scope_out();
+      },
+      StmtKind::DoWhile(do_while) => {
+        sema.loop_depth += 1;
+        do_while.stmt.check(sema)?;
+        sema.loop_depth -= 1;
+        // `do { } while (0)` runs once
+        sema.check_cond(&mut do_while.cond, |v| v == 0)?;
+      },
+      StmtKind::Switch(switch) => {
+        switch.cond.check(sema)?;
+        sema.expect_int(&switch.cond)?;
+        sema.scope_in();
+        sema.switch_depth += 1;
+        // labels so far, [None] for default
+        let mut labels: HashMap<Option<i32>, Span> = HashMap::new();
+        for case in &mut switch.cases {
+          // values that failed to evaluate are not compared
+          let label = match &mut case.value {
+            Some(value) => sema.int_const(value)?.map(Some),
+            None => Some(None),
+          };
+          if let Some(label) = label {
+            match labels.get(&label) {
+              Some(prev) => sema.report(FrontendError::DuplicateCase(label, case.span, *prev))?,
+              None => { labels.insert(label, case.span); },
+            }
+          }
+          case.items.check(sema)?;
+        }
+        sema.switch_depth -= 1;
+        sema.scope_out();
+      },
+      StmtKind::Break(_) if sema.loop_depth == 0 && sema.switch_depth == 0 => {
+        sema.report(FrontendError::BreakOutsideLoop(self.span))?;
+      },
+      StmtKind::Continue(_) if sema.loop_depth == 0 => {
+        sema.report(FrontendError::ContinueOutsideLoop(self.span))?;
+      },
+      StmtKind::Break(_) | StmtKind::Continue(_) => {},
+      StmtKind::Return(exp) => {
+        let (ident, ret_ty) = sema.function();
+        if let Some(exp) = exp {
+          exp.check(sema)?;
+          if ret_ty == Ty::Void {
+            sema.report(FrontendError::ReturnValueInVoid(ident, self.span))?;
+          } else {
+            sema.expect_scalar(exp, &ret_ty)?;
+          }
+        } else if ret_ty != Ty::Void {
+          sema.report(FrontendError::MissingReturnValue(ident, self.span))?;
+        }
+      },
+    }
+    Ok(())
+  }
+}
+
+impl Check for Exp {
+  type Out = ();
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    (self.ty, self.value) = match &mut self.kind {
+      ExpKind::Number(v) => match i32::try_from(*v) {
+        Ok(v) => (Ty::Int, Some(v)),
+        Err(_) => {
+          sema.report(FrontendError::LiteralTooLarge(self.span))?;
+          (Ty::Unknown, None)
+        },
+      },
+      ExpKind::FloatNumber(v) => (Ty::Float, Some(v.to_bits() as i32)),
+      ExpKind::Str(_) => (Ty::Str, None),
+      ExpKind::LVal(lval) => {
+        lval.check(sema)?;
+        match lval.symbol.map(|id| sema.symbol(id)) {
+          // scalar consts are inlined
+          Some(Symbol { ty: ty @ (Ty::Int | Ty::Float), is_const: true, value, .. }) if lval.indices.is_empty() => {
+            (value.map_or(Ty::Unknown, |_| ty.clone()), *value)
+          },
+          _ => (lval.ty(sema), None),
+        }
+      },
+      // folded whole, the literal alone is left as written
+      ExpKind::UnaryExp(UnaryOp::Neg, exp) if negated_int_literal(exp).is_some() => {
+        (Ty::Int, negated_int_literal(exp))
+      },
+      ExpKind::UnaryExp(uop, exp) => {
+        exp.check(sema)?;
+        match uop {
+          UnaryOp::BitNot => sema.expect_int(exp)?,
+          _ => sema.expect_arith(exp)?,
+        }
+        match (&*uop, &exp.ty) {
+          // flipping the sign bit, so that zero is negated as well
+          (UnaryOp::Neg, Ty::Float) => (Ty::Float, exp.value.map(|v| v ^ i32::MIN)),
+          (UnaryOp::Not, _) => (int_or_unknown(&[exp]), eval_unary(uop, exp.truth().map(i32::from))),
+          _ => {
+            if matches!(uop, UnaryOp::Neg) && exp.value == Some(i32::MIN) {
+              sema.warn(FrontendWarning::Overflow(self.span));
+            }
+            (int_or_unknown(&[exp]), eval_unary(uop, exp.value))
+          },
+        }
+      },
+      // operands of either type, compared with zero
+      ExpKind::BinaryExp(lhs, op @ (BinaryOp::And | BinaryOp::Or), rhs) => {
+        lhs.check(sema)?;
+        sema.expect_arith(lhs)?;
+        rhs.check(sema)?;
+        sema.expect_arith(rhs)?;
+        let value = eval_binary(op, lhs.truth().map(i32::from), rhs.truth().map(i32::from));
+        (int_or_unknown(&[lhs, rhs]), value)
+      },
+      ExpKind::BinaryExp(lhs, op, rhs) => {
+        lhs.check(sema)?;
+        sema.expect_operand(op, lhs)?;
+        rhs.check(sema)?;
+        sema.expect_operand(op, rhs)?;
+        let ty = if int_only(op) { int_or_unknown(&[lhs, rhs]) } else { arith_or_unknown(&[lhs, rhs]) };
+        if ty == Ty::Float {
+          // an int operand is converted, comparisons give an int
+          let (lv, rv) = (lhs.value.map(|v| convert(v, &lhs.ty, &ty)), rhs.value.map(|v| convert(v, &rhs.ty, &ty)));
+          let ty = if compares(op) { Ty::Int } else { ty };
+          (ty, eval_float_binary(op, lv, rv))
+        } else if matches!(op, BinaryOp::Div | BinaryOp::Mod) && rhs.value == Some(0) {
+          // outside constant expressions the division is left to run time
+          if sema.in_const_exp() {
+            sema.report(FrontendError::DivisionByZero(self.span))?;
+            (Ty::Unknown, None)
+          } else {
+            sema.warn(FrontendWarning::DivisionByZero(self.span));
+            (ty, None)
+          }
+        } else {
+          if let (Some(lv), Some(rv)) = (lhs.value, rhs.value) {
+            if overflows(op, lv, rv) {
+              sema.warn(FrontendWarning::Overflow(self.span));
+            }
+          }
+          if matches!(op, BinaryOp::Shl | BinaryOp::Shr) && rhs.value.is_some_and(|v| !(0..32).contains(&v)) {
+            sema.warn(FrontendWarning::ShiftCount(self.span));
+          }
+          (ty, eval_binary(op, lhs.value, rhs.value))
+        }
+      },
+      // of the type of the target, the value is converted on the store
+      ExpKind::Assign(assign) => {
+        let ty = assign.lval.check_target(sema)?;
+        assign.exp.check(sema)?;
+        match &assign.op {
+          Some(op) if int_only(op) && ty == Ty::Float => {
+            sema.report(FrontendError::InvalidValueType(assign.lval.span))?;
+          },
+          Some(op) => sema.expect_operand(op, &assign.exp)?,
+          None => sema.expect_arith(&assign.exp)?,
+        }
+        let int_div = ty != Ty::Float && assign.exp.ty == Ty::Int;
+        if matches!(assign.op, Some(BinaryOp::Div | BinaryOp::Mod)) && int_div && assign.exp.value == Some(0) {
+          sema.warn(FrontendWarning::DivisionByZero(self.span));
+        }
+        (if ty == Ty::Float { ty } else { Ty::Int }, None)
+      },
+      ExpKind::IncDec(lval, _) => {
+        let ty = lval.check_target(sema)?;
+        (if ty == Ty::Float { ty } else { Ty::Int }, None)
+      },
+      // both branches are void, or both are numbers, converted to float if either is
+      ExpKind::Cond(cond, then_exp, else_exp) => {
+        cond.check(sema)?;
+        sema.expect_arith(cond)?;
+        then_exp.check(sema)?;
+        else_exp.check(sema)?;
+        let ty = if then_exp.ty == Ty::Void && else_exp.ty == Ty::Void {
+          Ty::Void
+        } else {
+          sema.expect_arith(then_exp)?;
+          sema.expect_arith(else_exp)?;
+          match cond.ty {
+            Ty::Unknown => Ty::Unknown,
+            _ => arith_or_unknown(&[then_exp, else_exp]),
+          }
+        };
+        let value = cond.truth().and_then(|c| {
+          let exp = if c { then_exp } else { else_exp };
+          exp.value.map(|v| convert(v, &exp.ty, &ty))
+        });
+        (ty, value)
+      },
+      // not a constant, even with constant operands
+      ExpKind::Comma(lhs, rhs) => {
+        lhs.check(sema)?;
+        rhs.check(sema)?;
+        (rhs.ty.clone(), None)
+      },
+      ExpKind::FuncCall(call) => {
+        call.symbol = sema.get_func(&call.ident);
+        if call.symbol.is_none() {
+          sema.report(FrontendError::UndeclaredId(call.ident.clone(), self.span))?;
+        }
+        for arg in &mut call.args {
+          arg.check(sema)?;
+        }
+        match call.symbol.map(|id| sema.symbol(id)) {
+          Some(Symbol { ty: Ty::Func(ret_ty, params, variadic), span: def, .. }) => {
+            let (ret_ty, params, variadic, def) = (ret_ty.as_ref().clone(), params.clone(), *variadic, *def);
+            call.check_args(sema, &params, variadic, self.span, def)?;
+            (ret_ty, None)
+          },
+          _ => (Ty::Unknown, None),
+        }
+      },
+    };
+    if self.ty == Ty::Float {
+      sema.use_floats();
+    }
+    Ok(())
+  }
+}
+
+impl FuncCall {
+  // compare args with the signature of the callee, more args of a variadic one are ints or floats
+  fn check_args(&self, sema: &mut Sema, params: &[Ty], variadic: bool, span: Span, def: Option<Span>) -> Result<()> {
+    if self.args.len() < params.len() || (self.args.len() > params.len() && !variadic) {
+      let e = FrontendError::ArgCountMismatch(self.ident.clone(), params.len(), self.args.len(), span, def);
+      sema.report(e)?;
+    }
+    // ints and floats convert to each other
+    let scalar = |ty: &Ty| matches!(ty, Ty::Int | Ty::Float);
+    for (arg, param) in self.args.iter().zip(params) {
+      let converts = scalar(&arg.ty) && scalar(param);
+      if arg.ty != *param && !converts && arg.ty != Ty::Unknown && *param != Ty::Unknown {
+        sema.report(FrontendError::ArgTypeMismatch(param.clone(), arg.ty.clone(), arg.span))?;
+      }
+      if *param == Ty::Float {
+        sema.use_floats();
+      }
+    }
+    for arg in self.args.iter().skip(params.len()) {
+      sema.expect_arith(arg)?;
+    }
+    Ok(())
+  }
+}
+
+// Control Flow
+
+// statements among the items of a block or of a case
+fn stmts(items: &[BlockItem]) -> impl Iterator<Item = &Stmt> {
+  items.iter().filter_map(|item| match item {
+    BlockItem::Decl(_) => None,
+    BlockItem::Stmt(stmt) => Some(stmt),
+  })
+}
+
+// whether control can reach the end of the items
+fn falls_through(items: &[BlockItem]) -> bool {
+  stmts(items).all(Stmt::falls_through)
+}
+
+// a loop condition that is missing or always true, the loop is only left by a break
+fn endless(cond: Option<&Exp>) -> bool {
+  cond.is_none_or(|cond| cond.truth() == Some(true))
+}
+
+impl Block {
+  fn falls_through(&self) -> bool {
+    falls_through(&self.item)
+  }
+}
+
+impl Stmt {
+  fn falls_through(&self) -> bool {
+    match &self.kind {
+      StmtKind::Return(_) | StmtKind::Break(_) | StmtKind::Continue(_) => false,
+      StmtKind::Block(block) => block.falls_through(),
+      StmtKind::If(if_stmt) => match &if_stmt.else_stmt {
+        Some(else_stmt) => if_stmt.then_stmt.falls_through() || else_stmt.falls_through(),
+        None => true,
+      },
+      StmtKind::While(while_stmt) => !endless(Some(&while_stmt.cond)) || while_stmt.stmt.breaks(),
+      StmtKind::For(for_stmt) => !endless(for_stmt.cond.as_ref()) || for_stmt.stmt.breaks(),
+      // the condition is reached by falling through or continuing the body
+      StmtKind::DoWhile(do_while) => {
+        let stmt = &do_while.stmt;
+        stmt.breaks() || ((stmt.falls_through() || stmt.continues()) && !endless(Some(&do_while.cond)))
+      },
+      // without default, or falling through the last case, or breaking out of any
+      StmtKind::Switch(switch) => {
+        !switch.cases.iter().any(|case| case.value.is_none())
+          || switch.cases.last().is_none_or(|case| falls_through(&case.items))
+          || switch.cases.iter().any(|case| stmts(&case.items).any(Stmt::breaks))
+      },
+      _ => true,
+    }
+  }
+
+  // whether a break leaves the innermost loop or switch containing this statement
+  fn breaks(&self) -> bool {
+    match &self.kind {
+      StmtKind::Break(_) => true,
+      StmtKind::Block(block) => stmts(&block.item).any(Stmt::breaks),
+      StmtKind::If(if_stmt) => {
+        if_stmt.then_stmt.breaks() || if_stmt.else_stmt.as_ref().is_some_and(Stmt::breaks)
+      },
+      _ => false,
+    }
+  }
+
+  // whether a continue goes on with the innermost loop containing this statement
+  fn continues(&self) -> bool {
+    match &self.kind {
+      StmtKind::Continue(_) => true,
+      StmtKind::Block(block) => stmts(&block.item).any(Stmt::continues),
+      StmtKind::If(if_stmt) => {
+        if_stmt.then_stmt.continues() || if_stmt.else_stmt.as_ref().is_some_and(Stmt::continues)
+      },
+      StmtKind::Switch(switch) => switch.cases.iter().any(|case| stmts(&case.items).any(Stmt::continues)),
+      _ => false,
+    }
+  }
+}
+
+// result of an operator, ill-typed operands are not reported again
+fn int_or_unknown(operands: &[&Exp]) -> Ty {
+  if operands.iter().any(|e| e.ty == Ty::Unknown) { Ty::Unknown } else { Ty::Int }
+}
+
+// common type of numbers, an int is converted to float if the other one is a float
+fn arith_or_unknown(operands: &[&Exp]) -> Ty {
+  match int_or_unknown(operands) {
+    Ty::Int if operands.iter().any(|e| e.ty == Ty::Float) => Ty::Float,
+    ty => ty,
+  }
+}
+
+// operators taking ints only
+fn int_only(op: &BinaryOp) -> bool {
+  matches!(op, BinaryOp::Mod | BinaryOp::Shl | BinaryOp::Shr | BinaryOp::BitAnd | BinaryOp::BitXor | BinaryOp::BitOr)
+}
+
+// operators giving an int for operands of either type
+fn compares(op: &BinaryOp) -> bool {
+  matches!(op, BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::Neq)
+}
+
+// resolve an lval, telling whether it is a const
+impl Check for LVal {
+  type Out = bool;
+  fn check(&mut self, sema: &mut Sema) -> Result<Self::Out> {
+    self.symbol = sema.get_var(&self.ident);
+    match self.symbol {
+      Some(id) => { sema.used.insert(id); },
+      None => sema.report(FrontendError::UndeclaredId(self.ident.clone(), self.span))?,
+    }
+    for idx in &mut self.indices {
+      idx.check(sema)?;
+      sema.expect_int(idx)?;
+    }
+    let defined = self.symbol.is_some_and(|id| sema.symbol(id).ty != Ty::Unknown);
+    if defined && self.ty(sema) == Ty::Unknown {
+      sema.report(FrontendError::InvalidValueType(self.span))?;
+    }
+    Ok(self.symbol.is_some_and(|id| sema.symbol(id).is_const))
+  }
+}
+
+impl LVal {
+  // resolve an lval that is assigned to, which must be a scalar var, giving its type
+  fn check_target(&mut self, sema: &mut Sema) -> Result<Ty> {
+    let is_const = self.check(sema)?;
+    let ty = self.ty(sema);
+    if is_const || matches!(ty, Ty::Ptr(..)) {
+      sema.report(FrontendError::InvalidValueType(self.span))?;
+    }
+    Ok(ty)
+  }
+
+  // type after indexing, arrays decay to pointers
+  fn ty(&self, sema: &Sema) -> Ty {
+    let n = self.indices.len();
+    match self.symbol.map(|id| &sema.symbol(id).ty) {
+      Some(ty @ (Ty::Int | Ty::Float)) if n == 0 => ty.clone(),
+      Some(Ty::Array(btype, dims)) if n == dims.len() => (*btype).into(),
+      Some(Ty::Array(btype, dims)) if n < dims.len() => Ty::Ptr(*btype, dims[n + 1..].to_vec()),
+      Some(Ty::Ptr(btype, dims)) if n == 0 => Ty::Ptr(*btype, dims.clone()),
+      Some(Ty::Ptr(btype, dims)) if n == dims.len() + 1 => (*btype).into(),
+      Some(Ty::Ptr(btype, dims)) if n <= dims.len() => Ty::Ptr(*btype, dims[n..].to_vec()),
+      _ => Ty::Unknown,
+    }
+  }
+}
@@ -0,0 +1,91 @@
+use super::FrontendError;
+use super::ast::Span;
+
+use lalrpop_util::{ParseError, lexer::Token};
+
+// binary operators, only ever expected right after an expression
+const OPERATORS: [&str; 17] = [
+  "*", "/", "%", "<<", ">>", "<", ">", "<=", ">=", "==", "!=", "&", "^", "|", "&&", "||", "?",
+];
+
+// the other tokens that may continue one
+const CONTINUATIONS: [&str; 18] = [
+  "+", "-", ",", "(", "[", "++", "--", "=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+// tokens that start an expression, besides literals and identifiers
+const EXP_START_TOKENS: [&str; 7] = ["!", "(", "+", "-", "++", "--", "~"];
+
+// text of a literal terminal, e.g. `;` for "\";\""
+fn literal(terminal: &str) -> Option<&str> {
+  terminal.strip_prefix('"').and_then(|t| t.strip_suffix('"'))
+}
+
+// readable name of a terminal from the grammar
+fn token_name(terminal: &str) -> String {
+  if let Some(literal) = literal(terminal) {
+    format!("`{}`", literal)
+  } else if terminal.contains("a-z") {
+    "identifier".into()
+  } else if terminal.starts_with("r#\"\"") {
+    "string".into()
+  } else {
+    "number".into()
+  }
+}
+
+// e.g. "expected `;` after expression, found `b`"
+fn expected_message(expected: &[String], found: &str) -> String {
+  let is_exp_token = |t: &String| literal(t).is_some_and(|t| OPERATORS.contains(&t) || CONTINUATIONS.contains(&t));
+  let after_exp = expected.iter().any(|t| literal(t).is_some_and(|t| OPERATORS.contains(&t)));
+  // numbers are only ever expected as the start of an expression
+  let exp_start = expected.iter().any(|t| token_name(t) == "number");
+
+  let mut names: Vec<String> = Vec::new();
+  if exp_start {
+    names.push("expression".into());
+  }
+  for t in expected {
+    let starts_exp = literal(t).is_none_or(|t| EXP_START_TOKENS.contains(&t));
+    if (after_exp && is_exp_token(t)) || (exp_start && starts_exp) {
+      continue;
+    }
+    let name = token_name(t);
+    if !names.contains(&name) {
+      names.push(name);
+    }
+  }
+  let list = match names.len() {
+    0 => return format!("unexpected {}", found),
+    1 => names[0].clone(),
+    2 => format!("{} or {}", names[0], names[1]),
+    _ => format!("one of {}", names.join(", ")),
+  };
+  let context = if after_exp { " after expression" } else { "" };
+  format!("expected {}{}, found {}", list, context, found)
+}
+
+// errors of grammar actions, with the span they are about
+pub type ActionError = (&'static str, Span);
+
+pub fn syntax_error(e: ParseError<usize, Token, ActionError>, input: &str) -> FrontendError {
+  let (message, span) = match e {
+    ParseError::InvalidToken { location } => {
+      let c = input[location..].chars().next().unwrap_or(' ');
+      (format!("unknown character `{}`", c), Span::new(location, location + c.len_utf8()))
+    },
+    ParseError::UnrecognizedEOF { location, expected } => {
+      let message = expected_message(&expected, "end of file");
+      (message, Span::new(location, location))
+    },
+    ParseError::UnrecognizedToken { token: (l, t, r), expected } => {
+      let message = expected_message(&expected, &format!("`{}`", t.1));
+      (message, Span::new(l, r))
+    },
+    ParseError::ExtraToken { token: (l, t, r) } => {
+      (format!("unexpected `{}`", t.1), Span::new(l, r))
+    },
+    ParseError::User { error: (message, span) } => (message.into(), span),
+  };
+  FrontendError::ParseFailure(message, span)
+}
@@ -1,14 +1,7 @@
-use super::FrontendError;
-
-use super::gen::Result;
 use super::config::Config;
 
 use koopa::ir::builder_traits::*;
-use koopa::ir::{
-  Type,
-  TypeKind,
-  Value as IrValue,
-};
+use koopa::ir::Value as IrValue;
 
 pub enum Value {
   Nav, // Not a value
@@ -17,32 +10,34 @@ pub enum Value {
   APtr(IrValue), // Array Pointer Value
 }
 
+// conversions are checked by semantic analysis already
+#[allow(clippy::wrong_self_convention)]
 impl Value {
   // convert to right value
-  pub fn as_val(self, config: &mut Config) -> Result<IrValue> { 
+  pub fn as_val(self, config: &mut Config) -> IrValue { 
     match self {
-      Self::Nav => Err(FrontendError::InvalidValueType),
-      Self::Int(i) => Ok(i),
+      Self::Nav => unreachable!(),
+      Self::Int(i) => i,
       Self::Ptr(p) => {
         let load = config.new_value_builder().load(p);
         config.insert_instr(load);
-        Ok(load)
+        load
       },
-      Self::APtr(p) => Ok(p),
+      Self::APtr(p) => p,
     }
   }
   // convert to right integer value
-  pub fn as_int(self, config: &mut Config) -> Result<IrValue> {
+  pub fn as_int(self, config: &mut Config) -> IrValue {
     match self {
-      Self::APtr(_) => Err(FrontendError::InvalidValueType),
+      Self::APtr(_) => unreachable!(),
       _ => self.as_val(config),
     }
   }
   // concert to left pointer value
-  pub fn as_ptr(self) -> Result<IrValue> {
+  pub fn as_ptr(self) -> IrValue {
     match self {
-      Self::Ptr(p) => Ok(p),
-      _ => Err(FrontendError::InvalidValueType),
+      Self::Ptr(p) => p,
+      _ => unreachable!(),
     }
   }
 }
@@ -57,25 +52,17 @@ pub enum Initializer {
 
 impl Initializer {
   // [d1][d2] -> ((d2, d2), (d1, d1 * d2))
-  fn expand(ty: &Type) -> Vec<(usize, usize)> {
-    match ty.kind() {
-      TypeKind::Int32 => Vec::new(),
-      TypeKind::Array(base, length) => {
-        let mut v = Self::expand(base);
-        if v.len() == 0 {
-          v.push((*length, *length));
-        } else {
-          let last_length = v.last().unwrap().1;
-          v.push((*length, last_length * *length));
-        }
-        v
-      },
-      _ => unreachable!(),
+  fn expand(dims: &[usize]) -> Vec<(usize, usize)> {
+    let mut v: Vec<(usize, usize)> = Vec::new();
+    for &length in dims.iter().rev() {
+      let last_length = v.last().map_or(1, |l| l.1);
+      v.push((length, last_length * length));
     }
+    v
   }
 
   // get 1d array by filling zeros
-  fn fill(inits: Vec<Self>, lens: &[(usize, usize)]) -> Result<Self> {
+  fn fill(inits: Vec<Self>, lens: &[(usize, usize)]) -> Option<Self> {
     let mut filled = Vec::new();
     let size = lens.last().unwrap().1; // total number of values
     for init in inits {
@@ -88,7 +75,7 @@ impl Initializer {
           }
 
           if align == 0 { // not aligned to last level
-            return Err(FrontendError::InvalidInitializer);
+            return None;
           } else {
             match Self::fill(list, &lens[..align])? {
               Self::List(l) => filled.extend(l),
@@ -101,13 +88,13 @@ impl Initializer {
         },
       }
       if filled.len() > size {
-        return Err(FrontendError::InvalidInitializer);
+        return None;
       }
     }
     while filled.len() < size {
       filled.push(Self::Const(0));
     }
-    Ok(Self::List(filled))
+    Some(Self::List(filled))
   }
 
   // reshape array (must be filled already)
@@ -136,40 +123,37 @@ impl Initializer {
     }
   }
 
-  pub fn fit(self, ty: &Type) -> Result<Self> {
-    let lens = Self::expand(ty);
+  // shape the initializer as an array of [dims], [None] if it does not fit
+  pub fn fit(self, dims: &[usize]) -> Option<Self> {
+    let lens = Self::expand(dims);
     if lens.is_empty() {
       match self {
-        Self::Value(v) => Ok(Self::Value(v)),
-        Self::Const(v) => Ok(Self::Const(v)),
-        _ => Err(FrontendError::InvalidInitializer),
+        Self::List(_) => None,
+        _ => Some(self),
       }
     } else {
       match self {
-        Self::List(list) => Ok(Self::fill(list, &lens)?.reshape(&lens)),
-        _ => Err(FrontendError::InvalidInitializer),
+        Self::List(list) => Some(Self::fill(list, &lens)?.reshape(&lens)),
+        _ => None,
       }
     }
   }
 
-  pub fn as_const(&self, config: &mut Config) -> Result<IrValue> {
+  pub fn as_const(&self, config: &mut Config) -> IrValue {
     match self {
       Self::Const(i) => if config.is_global() {
-        Ok(config.global_new_value_builder().integer(*i))
+        config.global_new_value_builder().integer(*i)
       } else {
-        Ok(config.new_value_builder().integer(*i))
+        config.new_value_builder().integer(*i)
       },
-      Self::Value(_) => Err(FrontendError::EvalConstExpFail),
+      Self::Value(_) => unreachable!(),
       Self::List(list) => {
-        let init = list.into_iter().map(
-          |v| v.as_const(config)
-        ).collect::<Result<_>>()?;
-
-        Ok(if config.is_global() {
+        let init = list.iter().map(|v| v.as_const(config)).collect();
+        if config.is_global() {
           config.global_new_value_builder().aggregate(init)
         } else {
           config.new_value_builder().aggregate(init)
-        })
+        }
       }
     }
   }
@@ -182,7 +166,7 @@ impl Initializer {
       },
       Self::Value(value) => config.new_value_builder().store(*value, ptr),
       Self::List(list) => {
-        for (i, init) in list.into_iter().enumerate() {
+        for (i, init) in list.iter().enumerate() {
           let idx = config.new_value_builder().integer(i as i32);
           let new_ptr = config.new_value_builder().get_elem_ptr(ptr, idx);
           config.insert_instr(new_ptr);
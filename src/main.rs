@@ -1,13 +1,19 @@
 mod backend;
 mod frontend;
+mod passes;
+mod diagnostic;
 
-use frontend::FrontendError;
+use diagnostic::{Diagnostic, Format, Renderer};
+use frontend::{FrontendError, WARNINGS};
+use passes::{PassManager, CLEANUP, PASSES, PIPELINE};
 
 use std::io;
+use std::io::IsTerminal;
 use std::fmt;
 use std::env::args;
 use std::process::exit;
 use std::fs::{read_to_string};
+use std::collections::HashSet;
 use koopa::back::KoopaGenerator;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -20,26 +26,66 @@ fn main() {
 }
 
 fn compile() -> Result<()> {
-  let (mode, input, output) = parse()?;
+  let Args {
+    mode, input, output, diag_format, diag_color, error_limit, warnings: enabled, werror,
+    passes, dump_before, dump_after,
+  } = parse()?;
 
   // read input and generate ir
-  let input = read_to_string(input).map_err(Error::FileError)?;
-  let ir = frontend::generate_ir(input).map_err(|e| Error::FrontendError(e))?;
+  let source = read_to_string(&input).map_err(Error::FileError)?;
+  let mut warnings = Vec::new();
+  let result = frontend::generate_ir(&source, error_limit, &mut warnings);
+  warnings.retain(|w| enabled.contains(w.name()));
+
+  // report warnings and errors in source order
+  let mut diags: Vec<_> = warnings.iter().map(|w| Diagnostic::from_warning(w, werror)).collect();
+  let mut count = if werror { warnings.len() } else { 0 };
+  if let Err(errors) = &result {
+    diags.extend(errors.iter().map(Diagnostic::from));
+    count += errors.iter().filter(|e| !matches!(e, FrontendError::TooManyErrors)).count();
+  }
+  diags.sort_by_key(|d| d.span.map_or(usize::MAX, |span| span.start));
+  let renderer = Renderer::new(&input, &source, diag_format, diag_color);
+  let mut rendered: String = diags.iter().map(|d| renderer.render(d)).collect();
+
+  let mut ir = match result {
+    Ok(ir) if count == 0 => {
+      eprint!("{}", rendered);
+      ir
+    },
+    _ => {
+      if diag_format == Format::Text && count > 1 {
+        rendered += &format!("\nerror: aborting due to {} previous errors", count);
+      }
+      return Err(Error::FrontendError(rendered));
+    },
+  };
+
+  // the pipeline for optimized output unless passes are chosen
+  let passes = passes.unwrap_or_else(|| match mode {
+    Mode::Perf => PIPELINE.to_vec(),
+    _ => CLEANUP.to_vec(),
+  });
+  let mut passman = PassManager::new(&passes);
+  passman.dump_before(dump_before);
+  passman.dump_after(dump_after);
+  passman.run(&mut ir.program);
 
   match mode {
     Mode::Koopa => KoopaGenerator::from_path(output)
       .map_err(Error::FileError)?
-      .generate_on(&ir)
+      .generate_on(&ir.program)
       .map_err(Error::IOError)?,
-    Mode::Riscv => backend::generate_asm(&ir, &output)
+    Mode::Riscv => backend::generate_asm(&ir.program, &ir.strings, &ir.floats, &output, false)
+      .map_err(Error::FileError)?,
+    Mode::Perf => backend::generate_asm(&ir.program, &ir.strings, &ir.floats, &output, true)
       .map_err(Error::FileError)?,
-    Mode::Perf => { unimplemented!() },
   }
   Ok(())
 }
 
-/* parse command line args */
-fn parse() -> Result<(Mode, String, String)> {
+/* parse command line args: <mode> <input> -o <output> [options] */
+fn parse() -> Result<Args> {
   let mut args = args();
   args.next();
   if let (Some(mode), Some(input), Some(_o), Some(output)) = 
@@ -50,21 +96,94 @@ fn parse() -> Result<(Mode, String, String)> {
       "-perf" => Mode::Perf,
       _ => return Err(Error::InvalidArgs),
     };
-    Ok((mode, input, output))
+    let mut diag_format = Format::Text;
+    let mut diag_color = io::stderr().is_terminal();
+    let mut error_limit = 20;
+    let mut warnings: HashSet<_> = WARNINGS.iter().filter(|(_, on)| *on).map(|(name, _)| *name).collect();
+    let mut werror = false;
+    let mut passes = None;
+    let mut dump_before = HashSet::new();
+    let mut dump_after = HashSet::new();
+    for opt in args {
+      if let Some(limit) = opt.strip_prefix("-ferror-limit=") {
+        error_limit = limit.parse().map_err(|_| Error::InvalidArgs)?;
+        continue;
+      }
+      if let Some(names) = opt.strip_prefix("-passes=") {
+        let names = names.split(',').filter(|name| !name.is_empty());
+        passes = Some(names.map(pass_name).collect::<Result<_>>()?);
+        continue;
+      }
+      if let Some(name) = opt.strip_prefix("-fdump-before=") {
+        dump_before.insert(dump_name(name)?);
+        continue;
+      }
+      if let Some(name) = opt.strip_prefix("-fdump-after=") {
+        dump_after.insert(dump_name(name)?);
+        continue;
+      }
+      match opt.as_str() {
+        "-Wall" => warnings.extend(WARNINGS.iter().map(|(name, _)| *name)),
+        "-Werror" => werror = true,
+        "-w" => warnings.clear(),
+        _ if opt.starts_with("-Wno-") => { warnings.remove(warning_name(&opt[5..])?); },
+        _ if opt.starts_with("-W") => { warnings.insert(warning_name(&opt[2..])?); },
+        "-fdiagnostics-format=text" => diag_format = Format::Text,
+        "-fdiagnostics-format=json" => diag_format = Format::Json,
+        "-fdiagnostics-color=always" => diag_color = true,
+        "-fdiagnostics-color=never" => diag_color = false,
+        "-fdiagnostics-color=auto" => diag_color = io::stderr().is_terminal(),
+        _ => return Err(Error::InvalidArgs),
+      }
+    }
+    // no escape codes inside json strings
+    diag_color &= diag_format == Format::Text;
+    Ok(Args {
+      mode, input, output, diag_format, diag_color, error_limit, warnings, werror,
+      passes, dump_before, dump_after,
+    })
   } else {
     Err(Error::InvalidArgs)
   }
 }
 
+fn warning_name(name: &str) -> Result<&'static str> {
+  WARNINGS.iter().map(|(known, _)| *known).find(|known| *known == name).ok_or(Error::InvalidArgs)
+}
+
+fn pass_name(name: &str) -> Result<&'static str> {
+  PASSES.iter().map(|(known, _)| *known).find(|known| *known == name).ok_or(Error::InvalidArgs)
+}
+
+// a pass to dump the ir around, or every pass
+fn dump_name(name: &str) -> Result<&'static str> {
+  if name == "all" { Ok("all") } else { pass_name(name) }
+}
+
+struct Args {
+  mode: Mode,
+  input: String,
+  output: String,
+  diag_format: Format,
+  diag_color: bool,
+  error_limit: usize, // [0] for no limit
+  warnings: HashSet<&'static str>, // names of enabled warnings
+  werror: bool, // warnings are reported as errors
+  passes: Option<Vec<&'static str>>, // [None] for the default of the mode
+  dump_before: HashSet<&'static str>, // names of passes, or "all"
+  dump_after: HashSet<&'static str>,
+}
+
 enum Mode {
   Koopa,
   Riscv,
   Perf,
 }
 
+#[allow(clippy::enum_variant_names)]
 enum Error {
   InvalidArgs,
-  FrontendError(FrontendError),
+  FrontendError(String), // rendered diagnostics
   FileError(io::Error),
   IOError(io::Error),
 }
@@ -73,7 +192,7 @@ impl fmt::Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
       Self::InvalidArgs => write!(f, "[Invalid Args]"),
-      Self::FrontendError(e) => write!(f, "[Frontend Error]: {}", e),
+      Self::FrontendError(diag) => write!(f, "{}", diag.trim_end()),
       Self::FileError(e) => write!(f, "[File Error]: {}", e),
       Self::IOError(e) => write!(f, "[Io Error]: {}", e),
     }
@@ -0,0 +1,169 @@
+use super::FunctionPass;
+use super::float::Intrinsics;
+use super::util::{add_params, remove_values, replace_uses};
+
+use std::collections::{HashMap, HashSet};
+use koopa::ir::builder_traits::*;
+use koopa::ir::{
+  dfg::DataFlowGraph,
+  BasicBlock,
+  Function,
+  FunctionData,
+  Value,
+  ValueKind,
+};
+
+// Remove the instructions and block params nothing depends on, starting from control flow,
+// calls other than float intrinsics, returns and stores to memory that may be read.
+// Local allocs only written to, arrays included, are removed with their stores
+pub struct Dce;
+
+impl FunctionPass for Dce {
+  fn run_on(&mut self, _func: Function, data: &mut FunctionData, intrinsics: &Intrinsics) {
+    let live = mark(data, intrinsics);
+    let (renewed, params) = strip_params(data, &live);
+
+    let dead: Vec<_> = data.layout().bbs().nodes()
+      .flat_map(|node| node.insts().keys().copied())
+      .filter(|inst| !live.contains(inst))
+      .collect();
+    for &inst in &dead {
+      let bb = data.layout().parent_bb(inst).unwrap();
+      data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+    }
+    // dead values are only used by dead values
+    remove_values(data, dead);
+    replace_uses(data, &renewed);
+    for param in renewed.into_keys().chain(params) {
+      data.dfg_mut().remove_value(param);
+    }
+  }
+}
+
+// the alloc a pointer is derived from, if any
+fn root_alloc(dfg: &DataFlowGraph, ptr: Value) -> Option<Value> {
+  if ptr.is_global() {
+    return None;
+  }
+  match dfg.value(ptr).kind() {
+    ValueKind::Alloc(_) => Some(ptr),
+    ValueKind::GetElemPtr(gep) => root_alloc(dfg, gep.src()),
+    ValueKind::GetPtr(gp) => root_alloc(dfg, gp.src()),
+    _ => None,
+  }
+}
+
+// whether memory at a pointer is never read, only stored to
+fn only_written(dfg: &DataFlowGraph, ptr: Value) -> bool {
+  dfg.value(ptr).used_by().iter().all(|&user| match dfg.value(user).kind() {
+    ValueKind::Store(store) => store.value() != ptr,
+    ValueKind::GetElemPtr(_) | ValueKind::GetPtr(_) => only_written(dfg, user),
+    _ => false,
+  })
+}
+
+// live values, from the instructions with effects to the values they use,
+// an arg of a jump is live with the param it is passed to
+fn mark(data: &FunctionData, intrinsics: &Intrinsics) -> HashSet<Value> {
+  let dfg = data.dfg();
+  let mut param_of = HashMap::new();
+  for (&bb, node) in dfg.bbs() {
+    param_of.extend(node.params().iter().enumerate().map(|(i, &p)| (p, (bb, i))));
+  }
+
+  let mut live = HashSet::new();
+  let mut work = Vec::new();
+  for node in data.layout().bbs().nodes() {
+    for &inst in node.insts().keys() {
+      let root = match dfg.value(inst).kind() {
+        ValueKind::Store(store) => root_alloc(dfg, store.dest()).is_none_or(|alloc| !only_written(dfg, alloc)),
+        ValueKind::Call(call) => !intrinsics.contains_key(&call.callee()),
+        ValueKind::Return(_) | ValueKind::Branch(_) | ValueKind::Jump(_) => true,
+        _ => false,
+      };
+      if root && live.insert(inst) {
+        work.push(inst);
+      }
+    }
+  }
+
+  while let Some(value) = work.pop() {
+    let uses: Vec<Value> = match dfg.value(value).kind() {
+      ValueKind::Branch(br) => vec![br.cond()],
+      ValueKind::Jump(_) => vec![],
+      ValueKind::BlockArgRef(_) => {
+        let (bb, i) = param_of[&value];
+        dfg.bb(bb).used_by().iter().flat_map(|&user| edge_args(dfg, user, bb)).map(|args| args[i]).collect()
+      },
+      kind => kind.value_uses().collect(),
+    };
+    for used in uses {
+      if !used.is_global() && live.insert(used) {
+        work.push(used);
+      }
+    }
+  }
+  live
+}
+
+// args a jump or branch passes to a block, once for each edge to it
+fn edge_args(dfg: &DataFlowGraph, inst: Value, bb: BasicBlock) -> Vec<Vec<Value>> {
+  match dfg.value(inst).kind() {
+    ValueKind::Jump(jump) => vec![jump.args().to_vec()],
+    ValueKind::Branch(br) => {
+      let mut args = Vec::new();
+      if br.true_bb() == bb {
+        args.push(br.true_args().to_vec());
+      }
+      if br.false_bb() == bb {
+        args.push(br.false_args().to_vec());
+      }
+      args
+    },
+    _ => unreachable!(),
+  }
+}
+
+// rebuild the params of blocks with dead ones, as their indices change,
+// and drop the args passed to them, giving the live params to their
+// new ones and the dead params, all to remove
+fn strip_params(data: &mut FunctionData, live: &HashSet<Value>) -> (HashMap<Value, Value>, Vec<Value>) {
+  let mut renewed = HashMap::new();
+  let mut dead = Vec::new();
+  let bbs: Vec<_> = data.layout().bbs().keys().copied().collect();
+  for bb in bbs {
+    let mask: Vec<_> = data.dfg().bb(bb).params().iter().map(|p| live.contains(p)).collect();
+    if mask.iter().all(|&l| l) {
+      continue;
+    }
+    let old = std::mem::take(data.dfg_mut().bb_mut(bb).params_mut());
+    let (kept, removed): (Vec<_>, Vec<_>) = old.into_iter().partition(|p| live.contains(p));
+    let names = kept.iter().map(|&p| {
+      let param = data.dfg().value(p);
+      (param.name().clone(), param.ty().clone())
+    }).collect();
+    let new = add_params(data, bb, names);
+
+    let keep = |args: &[Value]| -> Vec<Value> {
+      args.iter().zip(&mask).filter(|(_, &l)| l).map(|(&a, _)| a).collect()
+    };
+    let users: Vec<_> = data.dfg().bb(bb).used_by().iter().copied().collect();
+    for user in users {
+      match data.dfg().value(user).kind().clone() {
+        ValueKind::Jump(jump) => {
+          data.dfg_mut().replace_value_with(user).jump_with_args(bb, keep(jump.args()));
+        },
+        ValueKind::Branch(br) => {
+          let true_args = if br.true_bb() == bb { keep(br.true_args()) } else { br.true_args().to_vec() };
+          let false_args = if br.false_bb() == bb { keep(br.false_args()) } else { br.false_args().to_vec() };
+          data.dfg_mut().replace_value_with(user)
+            .branch_with_args(br.cond(), br.true_bb(), br.false_bb(), true_args, false_args);
+        },
+        _ => unreachable!(),
+      }
+    }
+    renewed.extend(kept.into_iter().zip(new));
+    dead.extend(removed);
+  }
+  (renewed, dead)
+}
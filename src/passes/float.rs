@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use koopa::ir::{Function, Program};
+
+// the float intrinsics a program declares, by function
+pub type Intrinsics = HashMap<Function, &'static str>;
+
+const INTRINSICS: [&str; 14] = [
+  "__fadd", "__fsub", "__fmul", "__fdiv",
+  "__feq", "__fne", "__flt", "__fle", "__fgt", "__fge",
+  "__itof", "__ftoi", "__dlo", "__dhi",
+];
+
+// their names are reserved, no function of the source takes one
+pub fn intrinsics(program: &Program) -> Intrinsics {
+  program.funcs().iter().filter_map(|(&func, data)| {
+    let name = INTRINSICS.iter().find(|&&name| name == &data.name()[1..])?;
+    Some((func, *name))
+  }).collect()
+}
+
+// an intrinsic on constant args, as the f extension computes it
+pub fn eval(intrinsic: &str, args: &[i32]) -> i32 {
+  let arg = |i: usize| f32::from_bits(args[i] as u32);
+  // any nan computed is the canonical one
+  let float = |x: f32| if x.is_nan() { 0x7fc0_0000 } else { x.to_bits() as i32 };
+  let double = |x: f32| (x as f64).to_bits();
+  match intrinsic {
+    "__fadd" => float(arg(0) + arg(1)),
+    "__fsub" => float(arg(0) - arg(1)),
+    "__fmul" => float(arg(0) * arg(1)),
+    "__fdiv" => float(arg(0) / arg(1)),
+    "__feq" => (arg(0) == arg(1)) as i32,
+    "__fne" => (arg(0) != arg(1)) as i32,
+    "__flt" => (arg(0) < arg(1)) as i32,
+    "__fle" => (arg(0) <= arg(1)) as i32,
+    "__fgt" => (arg(0) > arg(1)) as i32,
+    "__fge" => (arg(0) >= arg(1)) as i32,
+    "__itof" => float(args[0] as f32),
+    // saturating, a nan gives the largest int
+    "__ftoi" if arg(0).is_nan() => i32::MAX,
+    "__ftoi" => arg(0) as i32,
+    "__dlo" => double(arg(0)) as i32,
+    "__dhi" => (double(arg(0)) >> 32) as i32,
+    _ => unreachable!(),
+  }
+}
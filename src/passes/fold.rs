@@ -0,0 +1,81 @@
+use super::util::replace_uses;
+
+use std::collections::HashMap;
+use super::FunctionPass;
+use super::float::Intrinsics;
+use koopa::ir::builder_traits::*;
+use koopa::ir::{
+  dfg::DataFlowGraph,
+  BinaryOp,
+  Function,
+  FunctionData,
+  Value,
+  ValueKind,
+};
+
+// evaluate a binary operation exactly as the risc-v backend computes it
+pub fn eval_binary(op: BinaryOp, l: i32, r: i32) -> i32 {
+  match op {
+    BinaryOp::NotEq => (l != r).into(),
+    BinaryOp::Eq => (l == r).into(),
+    BinaryOp::Gt => (l > r).into(),
+    BinaryOp::Lt => (l < r).into(),
+    BinaryOp::Ge => (l >= r).into(),
+    BinaryOp::Le => (l <= r).into(),
+    BinaryOp::Add => l.wrapping_add(r),
+    BinaryOp::Sub => l.wrapping_sub(r),
+    BinaryOp::Mul => l.wrapping_mul(r),
+    BinaryOp::Div => if r == 0 { -1 } else { l.wrapping_div(r) },
+    BinaryOp::Mod => if r == 0 { l } else { l.wrapping_rem(r) },
+    BinaryOp::And => l & r,
+    BinaryOp::Or => l | r,
+    BinaryOp::Xor => l ^ r,
+    BinaryOp::Shl => l.wrapping_shl(r as u32),
+    BinaryOp::Shr => (l as u32).wrapping_shr(r as u32) as i32,
+    BinaryOp::Sar => l.wrapping_shr(r as u32),
+  }
+}
+
+// integer value of a local constant operand, or one it is folded to
+fn as_const(dfg: &DataFlowGraph, folded: &HashMap<Value, Value>, value: Value) -> Option<i32> {
+  let value = folded.get(&value).copied().unwrap_or(value);
+  if value.is_global() {
+    return None;
+  }
+  match dfg.value(value).kind() {
+    ValueKind::Integer(i) => Some(i.value()),
+    _ => None,
+  }
+}
+
+// Fold binary instructions whose operands are both constants
+pub struct ConstFold;
+
+impl FunctionPass for ConstFold {
+  fn run_on(&mut self, _func: Function, data: &mut FunctionData, _intrinsics: &Intrinsics) {
+    let mut folded = HashMap::new();
+    let bbs: Vec<_> = data.layout().bbs().keys().copied().collect();
+    for bb in bbs {
+      let insts: Vec<_> = data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+      for inst in insts {
+        let value = match data.dfg().value(inst).kind() {
+          ValueKind::Binary(b) => as_const(data.dfg(), &folded, b.lhs())
+            .zip(as_const(data.dfg(), &folded, b.rhs()))
+            .map(|(l, r)| eval_binary(b.op(), l, r)),
+          _ => None,
+        };
+        if let Some(i) = value {
+          let int = data.dfg_mut().new_value().integer(i);
+          folded.insert(inst, int);
+        }
+      }
+    }
+    // no folded value is used once the others are rebuilt
+    replace_uses(data, &folded);
+    for &inst in folded.keys() {
+      let bb = data.layout().parent_bb(inst).unwrap();
+      data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+      data.dfg_mut().remove_value(inst);
+    }
+  }
+}
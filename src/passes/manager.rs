@@ -0,0 +1,94 @@
+use super::{dce, fold, mem2reg, sccp, simplify};
+use super::float::{intrinsics, Intrinsics};
+
+use std::collections::HashSet;
+use koopa::back::KoopaGenerator;
+use koopa::ir::{Function, FunctionData, Program};
+
+// a pass over the whole program
+pub trait ModulePass {
+  fn run_on(&mut self, program: &mut Program);
+}
+
+// a pass over each function with a body, declarations are skipped,
+// seeing which callees are float intrinsics
+pub trait FunctionPass {
+  fn run_on(&mut self, func: Function, data: &mut FunctionData, intrinsics: &Intrinsics);
+}
+
+// run a function pass as a module pass
+struct PerFunction<P: FunctionPass>(P);
+
+impl<P: FunctionPass> ModulePass for PerFunction<P> {
+  fn run_on(&mut self, program: &mut Program) {
+    let funcs: Vec<_> = program.func_layout().to_vec();
+    let intrinsics = intrinsics(program);
+    for func in funcs {
+      let data = program.func_mut(func);
+      if data.layout().entry_bb().is_some() {
+        self.0.run_on(func, data, &intrinsics);
+      }
+    }
+  }
+}
+
+type NewPass = fn() -> Box<dyn ModulePass>;
+
+// known passes by name
+pub const PASSES: [(&str, NewPass); 5] = [
+  ("mem2reg", || Box::new(PerFunction(mem2reg::Mem2Reg))),
+  ("fold", || Box::new(PerFunction(fold::ConstFold))),
+  ("sccp", || Box::new(PerFunction(sccp::Sccp))),
+  ("dce", || Box::new(PerFunction(dce::Dce))),
+  ("simplifycfg", || Box::new(PerFunction(simplify::SimplifyCfg))),
+];
+
+// passes run for optimized output, sccp folds all that fold does
+pub const PIPELINE: [&str; 4] = ["mem2reg", "sccp", "dce", "simplifycfg"];
+
+// passes run for any output, removing what the frontend leaves behind
+pub const CLEANUP: [&str; 1] = ["simplifycfg"];
+
+pub struct PassManager {
+  passes: Vec<(&'static str, Box<dyn ModulePass>)>,
+  dump_before: HashSet<&'static str>, // names of passes, or "all"
+  dump_after: HashSet<&'static str>,
+}
+
+impl PassManager {
+  // [names] must be known passes
+  pub fn new(names: &[&'static str]) -> Self {
+    let passes = names.iter().map(|&name| {
+      let (_, new) = PASSES.iter().find(|(known, _)| *known == name).unwrap();
+      (name, new())
+    }).collect();
+    Self { passes, dump_before: HashSet::new(), dump_after: HashSet::new() }
+  }
+
+  pub fn dump_before(&mut self, names: HashSet<&'static str>) {
+    self.dump_before = names;
+  }
+
+  pub fn dump_after(&mut self, names: HashSet<&'static str>) {
+    self.dump_after = names;
+  }
+
+  // run the passes in order, dumping the ir to stderr around those asked for
+  pub fn run(&mut self, program: &mut Program) {
+    for (name, pass) in &mut self.passes {
+      if self.dump_before.contains(name) || self.dump_before.contains("all") {
+        dump(program, &format!("before {}", name));
+      }
+      pass.run_on(program);
+      if self.dump_after.contains(name) || self.dump_after.contains("all") {
+        dump(program, &format!("after {}", name));
+      }
+    }
+  }
+}
+
+fn dump(program: &Program, title: &str) {
+  let mut gen = KoopaGenerator::new(Vec::new());
+  gen.generate_on(program).unwrap();
+  eprintln!("// ir {}\n{}", title, String::from_utf8(gen.writer()).unwrap());
+}
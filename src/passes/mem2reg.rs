@@ -0,0 +1,238 @@
+use super::util::{add_params, remove_unreachable, replace_uses, reverse_postorder, successors};
+
+use std::collections::{HashMap, HashSet};
+use super::FunctionPass;
+use super::float::Intrinsics;
+use koopa::ir::builder_traits::*;
+use koopa::ir::{
+  BasicBlock,
+  Function,
+  FunctionData,
+  Type,
+  TypeKind,
+  Value,
+  ValueKind,
+};
+
+// Promote scalar allocs that are only loaded and stored to ssa values,
+// with basic block params in place of phi nodes
+pub struct Mem2Reg;
+
+impl FunctionPass for Mem2Reg {
+  fn run_on(&mut self, _func: Function, data: &mut FunctionData, _intrinsics: &Intrinsics) {
+    // loads in unreachable blocks would never be renamed
+    remove_unreachable(data);
+    let slots = promotable(data);
+    if slots.is_empty() {
+      return;
+    }
+    let dom = Dominators::new(data);
+    let params = place_params(data, &dom, &slots);
+    rename(data, &dom, &slots, &params);
+    for slot in slots {
+      data.layout_mut().bb_mut(slot.bb).insts_mut().remove(&slot.alloc);
+      data.dfg_mut().remove_value(slot.alloc);
+    }
+  }
+}
+
+// an alloc to promote
+struct Slot {
+  alloc: Value,
+  bb: BasicBlock, // where the alloc is
+  ty: Type, // of the values stored
+  name: Option<String>,
+}
+
+// allocs of scalars whose address is only used to load and store,
+// in the entry block, which dominates every access to them
+fn promotable(data: &FunctionData) -> Vec<Slot> {
+  let dfg = data.dfg();
+  let bb = data.layout().entry_bb().unwrap();
+  let mut slots = Vec::new();
+  for &inst in data.layout().bbs().node(&bb).unwrap().insts().keys() {
+    let value = dfg.value(inst);
+    let ty = match (value.kind(), value.ty().kind()) {
+      (ValueKind::Alloc(_), TypeKind::Pointer(ty)) if !matches!(ty.kind(), TypeKind::Array(..)) => ty,
+      _ => continue,
+    };
+    let only_accessed = value.used_by().iter().all(|user| match dfg.value(*user).kind() {
+      ValueKind::Load(_) => true,
+      ValueKind::Store(store) => store.value() != inst,
+      _ => false,
+    });
+    if only_accessed {
+      slots.push(Slot { alloc: inst, bb, ty: ty.clone(), name: value.name().clone() });
+    }
+  }
+  slots
+}
+
+// dominator tree of the reachable blocks, by the algorithm of cooper, harvey and kennedy,
+// blocks are numbered in reverse postorder so the entry is 0
+struct Dominators {
+  order: Vec<BasicBlock>,
+  index: HashMap<BasicBlock, usize>,
+  children: Vec<Vec<usize>>,
+  frontier: Vec<HashSet<usize>>,
+}
+
+impl Dominators {
+  fn new(data: &FunctionData) -> Self {
+    let order = reverse_postorder(data);
+    let index: HashMap<_, _> = order.iter().enumerate().map(|(i, &bb)| (bb, i)).collect();
+    let mut preds = vec![Vec::new(); order.len()];
+    for (i, &bb) in order.iter().enumerate() {
+      for succ in successors(data, bb) {
+        preds[index[&succ]].push(i);
+      }
+    }
+
+    let mut idom = vec![usize::MAX; order.len()];
+    idom[0] = 0;
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for b in 1..order.len() {
+        let mut processed = preds[b].iter().copied().filter(|&p| idom[p] != usize::MAX);
+        let first = processed.next().unwrap();
+        let new_idom = processed.fold(first, |a, p| intersect(&idom, a, p));
+        if idom[b] != new_idom {
+          idom[b] = new_idom;
+          changed = true;
+        }
+      }
+    }
+
+    let mut children = vec![Vec::new(); order.len()];
+    let mut frontier = vec![HashSet::new(); order.len()];
+    for b in 1..order.len() {
+      children[idom[b]].push(b);
+      if preds[b].len() > 1 {
+        for &p in &preds[b] {
+          let mut runner = p;
+          while runner != idom[b] {
+            frontier[runner].insert(b);
+            runner = idom[runner];
+          }
+        }
+      }
+    }
+    Self { order, index, children, frontier }
+  }
+}
+
+// nearest common dominator, walking up from both blocks
+fn intersect(idom: &[usize], mut a: usize, mut b: usize) -> usize {
+  while a != b {
+    while a > b {
+      a = idom[a];
+    }
+    while b > a {
+      b = idom[b];
+    }
+  }
+  a
+}
+
+// params of the blocks on the iterated dominance frontier of the stores to each slot,
+// as (slot, param) by block
+fn place_params(data: &mut FunctionData, dom: &Dominators, slots: &[Slot]) -> HashMap<BasicBlock, Vec<(usize, Value)>> {
+  let mut block_of = HashMap::new();
+  for (&bb, node) in data.layout().bbs() {
+    block_of.extend(node.insts().keys().map(|&inst| (inst, dom.index[&bb])));
+  }
+
+  let mut needed = vec![Vec::new(); dom.order.len()];
+  for (s, slot) in slots.iter().enumerate() {
+    let mut work: Vec<usize> = data.dfg().value(slot.alloc).used_by().iter()
+      .filter(|user| matches!(data.dfg().value(**user).kind(), ValueKind::Store(_)))
+      .map(|user| block_of[user])
+      .collect();
+    let mut placed = HashSet::new();
+    while let Some(b) = work.pop() {
+      for &f in &dom.frontier[b] {
+        if placed.insert(f) {
+          needed[f].push(s);
+          work.push(f);
+        }
+      }
+    }
+  }
+
+  let mut params = HashMap::new();
+  for (b, mut needed) in needed.into_iter().enumerate().filter(|(_, needed)| !needed.is_empty()) {
+    needed.sort_unstable();
+    let bb = dom.order[b];
+    let names = needed.iter().map(|&s| (slots[s].name.clone(), slots[s].ty.clone())).collect();
+    params.insert(bb, needed.into_iter().zip(add_params(data, bb, names)).collect());
+  }
+  params
+}
+
+// walk the dominator tree with the current value of each slot,
+// replacing loads, dropping stores and passing the values to successors with params
+fn rename(data: &mut FunctionData, dom: &Dominators, slots: &[Slot], params: &HashMap<BasicBlock, Vec<(usize, Value)>>) {
+  let slot_of: HashMap<_, _> = slots.iter().enumerate().map(|(s, slot)| (slot.alloc, s)).collect();
+  // a slot not stored to on some path is undefined there
+  let value_of = |s: usize, current: &[Option<Value>], data: &mut FunctionData| {
+    current[s].unwrap_or_else(|| data.dfg_mut().new_value().undef(slots[s].ty.clone()))
+  };
+  let mut loaded = HashMap::new();
+  let mut stack = vec![(0, vec![None; slots.len()])];
+  while let Some((b, mut current)) = stack.pop() {
+    let bb = dom.order[b];
+    for &(s, param) in params.get(&bb).into_iter().flatten() {
+      current[s] = Some(param);
+    }
+
+    let insts: Vec<_> = data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    for inst in insts {
+      match data.dfg().value(inst).kind().clone() {
+        ValueKind::Load(load) if slot_of.contains_key(&load.src()) => {
+          let value = value_of(slot_of[&load.src()], &current, data);
+          loaded.insert(inst, value);
+          continue;
+        },
+        ValueKind::Store(store) if slot_of.contains_key(&store.dest()) => {
+          current[slot_of[&store.dest()]] = Some(store.value());
+        },
+        ValueKind::Jump(jump) => {
+          let mut args = jump.args().to_vec();
+          for &(s, _) in params.get(&jump.target()).into_iter().flatten() {
+            args.push(value_of(s, &current, data));
+          }
+          data.dfg_mut().replace_value_with(inst).jump_with_args(jump.target(), args);
+          continue;
+        },
+        ValueKind::Branch(br) => {
+          let (mut true_args, mut false_args) = (br.true_args().to_vec(), br.false_args().to_vec());
+          for &(s, _) in params.get(&br.true_bb()).into_iter().flatten() {
+            true_args.push(value_of(s, &current, data));
+          }
+          for &(s, _) in params.get(&br.false_bb()).into_iter().flatten() {
+            false_args.push(value_of(s, &current, data));
+          }
+          data.dfg_mut().replace_value_with(inst)
+            .branch_with_args(br.cond(), br.true_bb(), br.false_bb(), true_args, false_args);
+          continue;
+        },
+        _ => continue,
+      }
+      data.layout_mut().bb_mut(bb).insts_mut().remove(&inst);
+      data.dfg_mut().remove_value(inst);
+    }
+
+    for &child in &dom.children[b] {
+      stack.push((child, current.clone()));
+    }
+  }
+
+  // the loads are only removed once nothing uses them
+  replace_uses(data, &loaded);
+  for &load in loaded.keys() {
+    let bb = data.layout().parent_bb(load).unwrap();
+    data.layout_mut().bb_mut(bb).insts_mut().remove(&load);
+    data.dfg_mut().remove_value(load);
+  }
+}
@@ -0,0 +1,22 @@
+/*
+  middle end of the compiler:
+  - manager: function and module passes, run in a pipeline with optional dumps of the ir
+  - mem2reg: promotion of scalar allocs to ssa values, the other passes work on its output
+  - fold: constant folding on binary instructions
+  - sccp: sparse conditional constant propagation, folding the branches it decides
+  - dce: removal of dead instructions, block params and stores never read
+  - simplify: cleanup of the control flow graph left by the frontend
+  - util: helpers for rewriting koopa ir in place
+  - float: float intrinsics, calls without effects that sccp evaluates on constants
+*/
+
+mod manager;
+mod mem2reg;
+mod fold;
+mod sccp;
+mod dce;
+mod simplify;
+mod util;
+mod float;
+
+pub use manager::{FunctionPass, PassManager, CLEANUP, PASSES, PIPELINE};
@@ -0,0 +1,223 @@
+use super::FunctionPass;
+use super::float::{eval, Intrinsics};
+use super::fold::eval_binary;
+use super::util::{remove_unreachable, replace_uses};
+
+use std::collections::{HashMap, HashSet};
+use koopa::ir::builder_traits::*;
+use koopa::ir::{
+  BasicBlock,
+  Function,
+  FunctionData,
+  Value,
+  ValueKind,
+};
+
+// Sparse conditional constant propagation, by the algorithm of wegman and zadeck:
+// values are only evaluated in blocks found executable, so constants flowing
+// through block params and the branches they decide are found together
+pub struct Sccp;
+
+impl FunctionPass for Sccp {
+  fn run_on(&mut self, _func: Function, data: &mut FunctionData, intrinsics: &Intrinsics) {
+    let mut solver = Solver::new(data, intrinsics);
+    solver.solve();
+    let Solver { lattice, executable, .. } = solver;
+    rewrite(data, &lattice, &executable);
+  }
+}
+
+// what is known of a value
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Lattice {
+  Unknown, // not reached yet, or undefined
+  Const(i32),
+  Varying,
+}
+
+impl Lattice {
+  fn meet(self, other: Self) -> Self {
+    match (self, other) {
+      (Self::Unknown, x) | (x, Self::Unknown) => x,
+      (Self::Const(a), Self::Const(b)) if a == b => self,
+      _ => Self::Varying,
+    }
+  }
+}
+
+struct Solver<'f> {
+  data: &'f FunctionData,
+  intrinsics: &'f Intrinsics,
+  lattice: HashMap<Value, Lattice>,
+  executable: HashSet<BasicBlock>,
+  bb_work: Vec<BasicBlock>,
+  value_work: Vec<Value>, // values whose users are to be evaluated again
+}
+
+impl<'f> Solver<'f> {
+  fn new(data: &'f FunctionData, intrinsics: &'f Intrinsics) -> Self {
+    let entry = data.layout().entry_bb().unwrap();
+    Self {
+      data,
+      intrinsics,
+      lattice: HashMap::new(),
+      executable: HashSet::from([entry]),
+      bb_work: vec![entry],
+      value_work: Vec::new(),
+    }
+  }
+
+  // a branch still on an unknown condition once nothing changes is on an undefined one,
+  // it takes the false edge as a zero would, and solving goes on from there
+  fn solve(&mut self) {
+    loop {
+      self.propagate();
+      let undecided: Vec<_> = self.executable.iter().filter_map(|&bb| {
+        let inst = *self.data.layout().bbs().node(&bb).unwrap().insts().back_key().unwrap();
+        match self.data.dfg().value(inst).kind() {
+          ValueKind::Branch(br) if self.get(br.cond()) == Lattice::Unknown => Some(br.clone()),
+          _ => None,
+        }
+      }).collect();
+      let before = self.executable.len();
+      for br in undecided {
+        self.flow(br.false_bb(), br.false_args());
+      }
+      if self.executable.len() == before && self.value_work.is_empty() {
+        break;
+      }
+    }
+  }
+
+  fn propagate(&mut self) {
+    loop {
+      if let Some(bb) = self.bb_work.pop() {
+        let insts: Vec<_> = self.data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+        for inst in insts {
+          self.visit(inst);
+        }
+      } else if let Some(value) = self.value_work.pop() {
+        let data = self.data;
+        for &user in data.dfg().value(value).used_by() {
+          let bb = data.layout().parent_bb(user);
+          if bb.is_some_and(|bb| self.executable.contains(&bb)) {
+            self.visit(user);
+          }
+        }
+      } else {
+        break;
+      }
+    }
+  }
+
+  fn get(&self, value: Value) -> Lattice {
+    if value.is_global() {
+      return Lattice::Varying;
+    }
+    match self.data.dfg().value(value).kind() {
+      ValueKind::Integer(i) => Lattice::Const(i.value()),
+      ValueKind::Undef(_) => Lattice::Unknown,
+      ValueKind::FuncArgRef(_) => Lattice::Varying,
+      _ => self.lattice.get(&value).copied().unwrap_or(Lattice::Unknown),
+    }
+  }
+
+  fn raise(&mut self, value: Value, new: Lattice) {
+    let old = self.get(value);
+    let new = old.meet(new);
+    if new != old {
+      self.lattice.insert(value, new);
+      self.value_work.push(value);
+    }
+  }
+
+  // an edge taken, passing args to the params of the target
+  fn flow(&mut self, target: BasicBlock, args: &[Value]) {
+    let params = self.data.dfg().bb(target).params();
+    for (&param, &arg) in params.iter().zip(args) {
+      self.raise(param, self.get(arg));
+    }
+    if self.executable.insert(target) {
+      self.bb_work.push(target);
+    }
+  }
+
+  fn visit(&mut self, inst: Value) {
+    match self.data.dfg().value(inst).kind() {
+      ValueKind::Binary(b) => {
+        let value = match (self.get(b.lhs()), self.get(b.rhs())) {
+          (Lattice::Const(l), Lattice::Const(r)) => Lattice::Const(eval_binary(b.op(), l, r)),
+          (Lattice::Varying, _) | (_, Lattice::Varying) => Lattice::Varying,
+          _ => Lattice::Unknown,
+        };
+        self.raise(inst, value);
+      },
+      ValueKind::Branch(br) => match self.get(br.cond()) {
+        Lattice::Const(c) if c != 0 => self.flow(br.true_bb(), br.true_args()),
+        Lattice::Const(_) => self.flow(br.false_bb(), br.false_args()),
+        Lattice::Varying => {
+          self.flow(br.true_bb(), br.true_args());
+          self.flow(br.false_bb(), br.false_args());
+        },
+        Lattice::Unknown => {},
+      },
+      ValueKind::Jump(jump) => self.flow(jump.target(), jump.args()),
+      // float operations, evaluated as binary instructions are
+      ValueKind::Call(call) if self.intrinsics.contains_key(&call.callee()) => {
+        let args: Vec<_> = call.args().iter().map(|&arg| self.get(arg)).collect();
+        let value = if args.contains(&Lattice::Varying) {
+          Lattice::Varying
+        } else if args.contains(&Lattice::Unknown) {
+          Lattice::Unknown
+        } else {
+          let args: Vec<_> = args.iter().map(|arg| match arg {
+            Lattice::Const(c) => *c,
+            _ => unreachable!(),
+          }).collect();
+          Lattice::Const(eval(self.intrinsics[&call.callee()], &args))
+        };
+        self.raise(inst, value);
+      },
+      // loads, other calls and addresses
+      _ => self.raise(inst, Lattice::Varying),
+    }
+  }
+}
+
+// replace the values found constant, and the branches they decide
+fn rewrite(data: &mut FunctionData, lattice: &HashMap<Value, Lattice>, executable: &HashSet<BasicBlock>) {
+  for &bb in executable {
+    let inst = *data.layout().bbs().node(&bb).unwrap().insts().back_key().unwrap();
+    let br = match data.dfg().value(inst).kind() {
+      ValueKind::Branch(br) => br.clone(),
+      _ => continue,
+    };
+    let known = match data.dfg().value(br.cond()).kind() {
+      ValueKind::Integer(i) => Lattice::Const(i.value()),
+      _ => lattice.get(&br.cond()).copied().unwrap_or(Lattice::Unknown),
+    };
+    let (target, args) = match known {
+      Lattice::Const(c) if c != 0 => (br.true_bb(), br.true_args().to_vec()),
+      // undefined, taken as zero by the solver
+      Lattice::Const(_) | Lattice::Unknown => (br.false_bb(), br.false_args().to_vec()),
+      Lattice::Varying => continue,
+    };
+    data.dfg_mut().replace_value_with(inst).jump_with_args(target, args);
+  }
+  remove_unreachable(data);
+
+  let mut replaced = HashMap::new();
+  for (&value, &known) in lattice {
+    if let Lattice::Const(c) = known {
+      replaced.insert(value, data.dfg_mut().new_value().integer(c));
+    }
+  }
+  replace_uses(data, &replaced);
+  // params stay until dce, without uses
+  for &value in replaced.keys() {
+    if let Some(bb) = data.layout().parent_bb(value) {
+      data.layout_mut().bb_mut(bb).insts_mut().remove(&value);
+      data.dfg_mut().remove_value(value);
+    }
+  }
+}
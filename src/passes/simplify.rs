@@ -0,0 +1,154 @@
+use super::FunctionPass;
+use super::float::Intrinsics;
+use super::util::{remove_unreachable, replace_uses};
+
+use std::collections::HashMap;
+use koopa::ir::builder_traits::*;
+use koopa::ir::{
+  BasicBlock,
+  Function,
+  FunctionData,
+  Value,
+  ValueKind,
+};
+
+// Simplify the control flow graph until nothing changes:
+// fold constant branches, drop unreachable blocks, thread jumps through
+// blocks that only jump, and merge blocks into their single predecessor
+pub struct SimplifyCfg;
+
+impl FunctionPass for SimplifyCfg {
+  fn run_on(&mut self, _func: Function, data: &mut FunctionData, _intrinsics: &Intrinsics) {
+    let mut changed = true;
+    while changed {
+      changed = fold_branches(data);
+      let count = data.layout().bbs().len();
+      remove_unreachable(data);
+      changed |= data.layout().bbs().len() != count;
+      changed |= thread_jumps(data);
+      changed |= merge_blocks(data);
+    }
+  }
+}
+
+fn terminator(data: &FunctionData, bb: BasicBlock) -> Value {
+  *data.layout().bbs().node(&bb).unwrap().insts().back_key().unwrap()
+}
+
+// branches on a constant, or to the same block with the same args, become jumps
+fn fold_branches(data: &mut FunctionData) -> bool {
+  let mut changed = false;
+  let bbs: Vec<_> = data.layout().bbs().keys().copied().collect();
+  for bb in bbs {
+    let inst = terminator(data, bb);
+    let br = match data.dfg().value(inst).kind() {
+      ValueKind::Branch(br) => br.clone(),
+      _ => continue,
+    };
+    let taken = match data.dfg().value(br.cond()).kind() {
+      ValueKind::Integer(i) => i.value() != 0,
+      _ if br.true_bb() == br.false_bb() && br.true_args() == br.false_args() => true,
+      _ => continue,
+    };
+    let (target, args) = if taken {
+      (br.true_bb(), br.true_args().to_vec())
+    } else {
+      (br.false_bb(), br.false_args().to_vec())
+    };
+    data.dfg_mut().replace_value_with(inst).jump_with_args(target, args);
+    changed = true;
+  }
+  changed
+}
+
+// blocks whose only instruction is a jump elsewhere, as (target, args),
+// and whose params are not used by the blocks they dominate
+fn forwarding(data: &FunctionData, bb: BasicBlock) -> Option<(BasicBlock, Vec<Value>)> {
+  let insts = data.layout().bbs().node(&bb).unwrap().insts();
+  if insts.len() != 1 || data.layout().entry_bb() == Some(bb) {
+    return None;
+  }
+  let inst = *insts.front_key().unwrap();
+  let params = data.dfg().bb(bb).params();
+  if params.iter().any(|&p| data.dfg().value(p).used_by().iter().any(|&user| user != inst)) {
+    return None;
+  }
+  match data.dfg().value(inst).kind() {
+    ValueKind::Jump(jump) if jump.target() != bb => Some((jump.target(), jump.args().to_vec())),
+    _ => None,
+  }
+}
+
+// jump straight to the target of a forwarding block, with its params bound to the args
+fn thread_jumps(data: &mut FunctionData) -> bool {
+  let mut changed = false;
+  let bbs: Vec<_> = data.layout().bbs().keys().copied().collect();
+  for bb in bbs {
+    let Some((target, args)) = forwarding(data, bb) else { continue };
+    let params = data.dfg().bb(bb).params().to_vec();
+    let thread = |(to, passed): (BasicBlock, Vec<Value>)| {
+      if to != bb {
+        return (to, passed);
+      }
+      let args = args.iter().map(|arg| match params.iter().position(|p| p == arg) {
+        Some(i) => passed[i],
+        None => *arg,
+      }).collect();
+      (target, args)
+    };
+    let users: Vec<_> = data.dfg().bb(bb).used_by().iter().copied().collect();
+    for user in users {
+      match data.dfg().value(user).kind().clone() {
+        ValueKind::Jump(jump) => {
+          let (to, args) = thread((jump.target(), jump.args().to_vec()));
+          data.dfg_mut().replace_value_with(user).jump_with_args(to, args);
+        },
+        ValueKind::Branch(br) => {
+          let (t, t_args) = thread((br.true_bb(), br.true_args().to_vec()));
+          let (f, f_args) = thread((br.false_bb(), br.false_args().to_vec()));
+          data.dfg_mut().replace_value_with(user).branch_with_args(br.cond(), t, f, t_args, f_args);
+        },
+        _ => unreachable!(),
+      }
+      changed = true;
+    }
+  }
+  changed
+}
+
+// append a block to its single predecessor when that ends with a jump to it
+fn merge_blocks(data: &mut FunctionData) -> bool {
+  let mut bound = HashMap::new(); // params of merged blocks to their args
+  let mut merged = Vec::new();
+  let bbs: Vec<_> = data.layout().bbs().keys().copied().collect();
+  for pred in bbs {
+    // may have been merged into an earlier block
+    if data.layout().bbs().node(&pred).is_none() {
+      continue;
+    }
+    loop {
+      let jump = terminator(data, pred);
+      let (bb, args) = match data.dfg().value(jump).kind() {
+        ValueKind::Jump(j) if j.target() != pred => (j.target(), j.args().to_vec()),
+        _ => break,
+      };
+      if data.dfg().bb(bb).used_by().len() != 1 || data.layout().entry_bb() == Some(bb) {
+        break;
+      }
+      bound.extend(data.dfg().bb(bb).params().iter().copied().zip(args));
+      data.layout_mut().bb_mut(pred).insts_mut().remove(&jump);
+      data.dfg_mut().remove_value(jump);
+      let (_, mut node) = data.layout_mut().bbs_mut().remove(&bb).unwrap();
+      while let Some((inst, _)) = node.insts_mut().pop_front() {
+        data.layout_mut().bb_mut(pred).insts_mut().push_key_back(inst).unwrap();
+      }
+      merged.push(bb);
+    }
+  }
+  // the params go with their blocks once nothing uses them
+  replace_uses(data, &bound);
+  for &bb in &merged {
+    data.dfg_mut().remove_bb(bb);
+  }
+  !merged.is_empty()
+}
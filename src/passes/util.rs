@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+use koopa::ir::builder_traits::*;
+use koopa::ir::{
+  BasicBlock,
+  FunctionData,
+  Type,
+  Value,
+  ValueKind,
+};
+
+// apply `f` on every value operand of an instruction
+pub fn map_operands(kind: &mut ValueKind, f: impl Fn(Value) -> Value) {
+  match kind {
+    ValueKind::Load(v) => *v.src_mut() = f(v.src()),
+    ValueKind::Store(v) => {
+      *v.value_mut() = f(v.value());
+      *v.dest_mut() = f(v.dest());
+    },
+    ValueKind::GetPtr(v) => {
+      *v.src_mut() = f(v.src());
+      *v.index_mut() = f(v.index());
+    },
+    ValueKind::GetElemPtr(v) => {
+      *v.src_mut() = f(v.src());
+      *v.index_mut() = f(v.index());
+    },
+    ValueKind::Binary(v) => {
+      *v.lhs_mut() = f(v.lhs());
+      *v.rhs_mut() = f(v.rhs());
+    },
+    ValueKind::Branch(v) => {
+      *v.cond_mut() = f(v.cond());
+      v.true_args_mut().iter_mut().for_each(|a| *a = f(*a));
+      v.false_args_mut().iter_mut().for_each(|a| *a = f(*a));
+    },
+    ValueKind::Jump(v) => v.args_mut().iter_mut().for_each(|a| *a = f(*a)),
+    ValueKind::Call(v) => v.args_mut().iter_mut().for_each(|a| *a = f(*a)),
+    ValueKind::Return(v) => *v.value_mut() = v.value().map(&f),
+    _ => {},
+  }
+}
+
+// redirect the uses of values to their replacements, following chains of them.
+// koopa drops the `used_by` set of a rebuilt value, so every instruction is rebuilt
+// once, after the values it uses, and registers itself with its operands again
+pub fn replace_uses(data: &mut FunctionData, replaced: &HashMap<Value, Value>) {
+  if replaced.is_empty() {
+    return;
+  }
+  let resolve = |mut value: Value| {
+    while let Some(&next) = replaced.get(&value) {
+      value = next;
+    }
+    value
+  };
+  // reachable blocks in reverse postorder define values before using them
+  let mut order = reverse_postorder(data);
+  let reachable: HashSet<_> = order.iter().copied().collect();
+  order.extend(data.layout().bbs().keys().filter(|bb| !reachable.contains(bb)));
+  for bb in order {
+    let insts: Vec<_> = data.layout().bbs().node(&bb).unwrap().insts().keys().copied().collect();
+    for inst in insts {
+      let mut value = data.dfg().value(inst).clone();
+      map_operands(value.kind_mut(), resolve);
+      data.dfg_mut().replace_value_with(inst).raw(value);
+    }
+  }
+}
+
+// koopa only creates params along with a block, so they are taken from a new one
+pub fn add_params(data: &mut FunctionData, bb: BasicBlock, params: Vec<(Option<String>, Type)>) -> Vec<Value> {
+  let donor = data.dfg_mut().new_bb().basic_block_with_param_names(None, params);
+  let params = std::mem::take(data.dfg_mut().bb_mut(donor).params_mut());
+  data.dfg_mut().remove_bb(donor);
+  data.dfg_mut().bb_mut(bb).params_mut().extend(params.iter().copied());
+  params
+}
+
+// successors of a basic block, by its terminator
+pub fn successors(data: &FunctionData, bb: BasicBlock) -> Vec<BasicBlock> {
+  let last = data.layout().bbs().node(&bb).unwrap().insts().back_key();
+  match last.map(|inst| data.dfg().value(*inst).kind()) {
+    Some(ValueKind::Branch(b)) => vec![b.true_bb(), b.false_bb()],
+    Some(ValueKind::Jump(j)) => vec![j.target()],
+    _ => vec![],
+  }
+}
+
+// basic blocks reachable from the entry, in reverse postorder
+pub fn reverse_postorder(data: &FunctionData) -> Vec<BasicBlock> {
+  let entry = data.layout().entry_bb().unwrap();
+  let mut order = Vec::new();
+  let mut visited = HashSet::from([entry]);
+  let mut stack = vec![(entry, successors(data, entry))];
+  while let Some((bb, succs)) = stack.last_mut() {
+    match succs.pop() {
+      Some(succ) => if visited.insert(succ) {
+        stack.push((succ, successors(data, succ)));
+      },
+      None => {
+        order.push(*bb);
+        stack.pop();
+      },
+    }
+  }
+  order.reverse();
+  order
+}
+
+// remove the basic blocks never reached from the entry, with their instructions
+pub fn remove_unreachable(data: &mut FunctionData) {
+  let reachable: HashSet<_> = reverse_postorder(data).into_iter().collect();
+  let dead: Vec<_> = data.layout().bbs().keys().filter(|bb| !reachable.contains(bb)).copied().collect();
+  let mut insts = Vec::new();
+  for bb in &dead {
+    let (_, node) = data.layout_mut().bbs_mut().remove(bb).unwrap();
+    insts.extend(node.insts().keys().copied());
+  }
+  // an alloc declared where no edge leads is still used past it, as by a case
+  // jumped to after the declaration, it moves to the entry
+  let removed: HashSet<_> = insts.iter().copied().collect();
+  let entry = data.layout().entry_bb().unwrap();
+  insts.retain(|&inst| {
+    let value = data.dfg().value(inst);
+    let escapes = matches!(value.kind(), ValueKind::Alloc(_))
+      && value.used_by().iter().any(|user| !removed.contains(user));
+    if escapes {
+      data.layout_mut().bb_mut(entry).insts_mut().push_key_front(inst).unwrap();
+    }
+    !escapes
+  });
+  remove_values(data, insts);
+  for bb in dead {
+    data.dfg_mut().remove_bb(bb);
+  }
+}
+
+// remove values out of the layout, used by none but each other,
+// each after all its users, found by a depth first walk over them
+pub fn remove_values(data: &mut FunctionData, values: Vec<Value>) {
+  let removed: HashSet<_> = values.iter().copied().collect();
+  let users = |value: Value| -> Vec<Value> {
+    let users = data.dfg().value(value).used_by();
+    assert!(users.iter().all(|user| removed.contains(user)), "removed value still in use");
+    users.iter().copied().collect()
+  };
+  let mut order = Vec::new();
+  let mut visited = HashSet::new();
+  for value in values {
+    if !visited.insert(value) {
+      continue;
+    }
+    let mut stack = vec![(value, users(value))];
+    while let Some((value, next)) = stack.last_mut() {
+      match next.pop() {
+        Some(user) => if visited.insert(user) {
+          stack.push((user, users(user)));
+        },
+        None => {
+          order.push(*value);
+          stack.pop();
+        },
+      }
+    }
+  }
+  for value in order {
+    data.dfg_mut().remove_value(value);
+  }
+}
@@ -0,0 +1,485 @@
+// negative tests, running the compiler on programs it must reject
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// compile [source] to koopa, returning the exit status and stderr
+fn compile(name: &str, source: &str) -> (bool, String) {
+  compile_with(name, source, &[])
+}
+
+fn compile_with(name: &str, source: &str, flags: &[&str]) -> (bool, String) {
+  let dir = std::env::temp_dir().join(format!("compiler-rs-test-{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let input: PathBuf = dir.join(format!("{}.sy", name));
+  fs::write(&input, source).unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_compiler-rs"))
+    .arg("-koopa")
+    .arg(&input)
+    .arg("-o")
+    .arg(dir.join(format!("{}.koopa", name)))
+    .arg("-fdiagnostics-color=never")
+    .args(flags)
+    .output()
+    .unwrap();
+  (output.status.success(), String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn break_outside_loop() {
+  let (ok, stderr) = compile("break", "int main() {\n  break;\n  return 0;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: `break` outside of a loop"), "{}", stderr);
+  assert!(stderr.contains("break.sy:2:3"), "{}", stderr);
+}
+
+#[test]
+fn continue_outside_loop() {
+  let (ok, stderr) = compile("continue", "int main() {\n  if (1) continue;\n  return 0;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: `continue` outside of a loop"), "{}", stderr);
+  assert!(stderr.contains("continue.sy:2:10"), "{}", stderr);
+}
+
+#[test]
+fn break_in_function_called_from_loop() {
+  let source = "void f() { break; }\nint main() {\n  while (1) f();\n  return 0;\n}\n";
+  let (ok, stderr) = compile("callee", source);
+  assert!(!ok);
+  assert!(stderr.contains("callee.sy:1:12"), "{}", stderr);
+}
+
+#[test]
+fn break_and_continue_inside_loops() {
+  let source = "int main() {\n  int i = 0;\n  while (i < 10) {\n    i = i + 1;\n    if (i < 3) continue;\n    { if (i > 5) break; }\n  }\n  return i;\n}\n";
+  let (ok, stderr) = compile("loops", source);
+  assert!(ok, "{}", stderr);
+}
+
+const UNUSED: &str = "int main() {\n  int x;\n  return 0;\n  x = 1;\n}\n";
+
+#[test]
+fn warnings_do_not_stop_compilation() {
+  let (ok, stderr) = compile("warn", UNUSED);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.contains("warning: unreachable code [-Wunreachable-code]"), "{}", stderr);
+  assert!(stderr.contains("warn.sy:4:3"), "{}", stderr);
+}
+
+#[test]
+fn warnings_can_be_disabled() {
+  let (ok, stderr) = compile_with("quiet", UNUSED, &["-Wno-unreachable-code"]);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.is_empty(), "{}", stderr);
+}
+
+#[test]
+fn werror_promotes_warnings() {
+  let source = "int g;\nint main() {\n  int g = 1;\n  return g;\n}\n";
+  let (ok, stderr) = compile_with("werror", source, &["-Wshadow", "-Werror"]);
+  assert!(!ok);
+  assert!(stderr.contains("error: declaration of `g` shadows an outer one [-Werror=shadow]"), "{}", stderr);
+  assert!(stderr.contains("werror.sy:1:5"), "{}", stderr);
+}
+
+#[test]
+fn unused_results_of_any_type() {
+  let source = "float f() { return 1.0; }\nvoid g() { }\nint main() {\n  getint();\n  getfloat();\n  f();\n  g();\n  return 0;\n}\n";
+  let (ok, stderr) = compile_with("unused", source, &["-Wunused-result"]);
+  assert!(ok, "{}", stderr);
+  assert_eq!(stderr.matches("[-Wunused-result]").count(), 3, "{}", stderr);
+  assert!(stderr.contains("warning: result of `getfloat` is unused"), "{}", stderr);
+  assert!(stderr.contains("unused.sy:5:3"), "{}", stderr);
+  assert!(stderr.contains("warning: result of `f` is unused"), "{}", stderr);
+  assert!(!stderr.contains("`g`"), "{}", stderr);
+}
+
+#[test]
+fn division_by_zero_in_constant() {
+  let (ok, stderr) = compile("divzero", "const int n = 1 / (2 - 2);\nint main() {\n  return n;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: division by zero in a constant expression"), "{}", stderr);
+  assert!(stderr.contains("divzero.sy:1:15"), "{}", stderr);
+}
+
+#[test]
+fn array_dims_out_of_range() {
+  let (ok, stderr) = compile("dims", "int a[65536][65536];\nint b[0];\nint main() {\n  return 0;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: array is too large"), "{}", stderr);
+  assert!(stderr.contains("dims.sy:1:14"), "{}", stderr);
+  assert!(stderr.contains("error: array dimension must be positive"), "{}", stderr);
+  assert!(stderr.contains("dims.sy:2:7"), "{}", stderr);
+}
+
+#[test]
+fn int_literals() {
+  let source = "int main() {\n  int a = -2147483648;\n  int b = 0xFFFFFFFF;\n  return a + b;\n}\n";
+  let (ok, stderr) = compile("literals", source);
+  assert!(ok, "{}", stderr);
+  let (ok, stderr) = compile("too_large", "int main() {\n  return 2147483648;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: integer literal is too large for `int`"), "{}", stderr);
+  assert!(stderr.contains("too_large.sy:2:10"), "{}", stderr);
+}
+
+#[test]
+fn duplicate_case_labels() {
+  let source = "int main() {\n  switch (getint()) {\n    case 1: break;\n    case 1: break;\n  }\n  return 0;\n}\n";
+  let (ok, stderr) = compile("cases", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: duplicate case value `1`"), "{}", stderr);
+  assert!(stderr.contains("cases.sy:4:5"), "{}", stderr);
+  assert!(stderr.contains("cases.sy:3:5"), "{}", stderr);
+}
+
+#[test]
+fn break_and_continue_in_for_do_and_switch() {
+  let source = "int main() {\n  int s = 0;\n  for (int i = 0; i < 9; i = i + 1) {\n    switch (i) {\n      case 2: continue;\n      default: break;\n    }\n    do { s = s + i; if (s > 20) break; } while (0);\n  }\n  return s;\n}\n";
+  let (ok, stderr) = compile("jumps", source);
+  assert!(ok, "{}", stderr);
+}
+
+#[test]
+fn compound_assignment_targets() {
+  let source = "int main() {\n  const int c = 1;\n  int a[2], i = 0;\n  a[i++] += 1;\n  c++;\n  for (i = 0, c -= 1; i < 2; i++) ;\n  return 0;\n}\n";
+  let (ok, stderr) = compile("targets", source);
+  assert!(!ok);
+  assert!(stderr.contains("targets.sy:5:3"), "{}", stderr);
+  assert!(stderr.contains("targets.sy:6:15"), "{}", stderr);
+  assert!(!stderr.contains("targets.sy:4:"), "{}", stderr);
+}
+
+#[test]
+fn bitwise_operators_and_shift_count() {
+  let source = "const int n = ~0 & 0xF0 | 1 << 3;\nint a[n ^ 1];\nint main() {\n  int x = getint();\n  x >>= 2;\n  return x << 32;\n}\n";
+  let (ok, stderr) = compile("bits", source);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.contains("warning: shift count is negative or not less than the width of `int`"), "{}", stderr);
+  assert!(stderr.contains("bits.sy:6:10"), "{}", stderr);
+}
+
+#[test]
+fn conditional_operator() {
+  let source = "const int n = 1 ? 4 : 8;\nint a[n > 2 ? n : 1];\nint main() {\n  int x = getint();\n  x ? putint(1) : putint(2);\n  return x ? putint(x) : 0;\n}\n";
+  let (ok, stderr) = compile("cond", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: void value used in an expression"), "{}", stderr);
+  assert!(stderr.contains("cond.sy:6:14"), "{}", stderr);
+  assert_eq!(stderr.matches("error:").count(), 1, "{}", stderr);
+}
+
+#[test]
+fn float_operands() {
+  let source = "const float h = 0x1.8p1;\nint a[2.5];\nint main() {\n  float f = h * 2 + 1e-1, b[2];\n  int i = f;\n  i += .5;\n  f %= 2;\n  return getarray(b);\n}\n";
+  let (ok, stderr) = compile("float", source);
+  assert!(!ok);
+  assert!(stderr.contains("float.sy:2:7"), "{}", stderr);
+  assert!(stderr.contains("float.sy:7:3"), "{}", stderr);
+  assert!(stderr.contains("error: mismatched argument type: expected `int[]`, found `float[]`"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 3 previous errors"), "{}", stderr);
+}
+
+#[test]
+fn string_arguments() {
+  let source = "int main() {\n  int a[2];\n  putf(\"%d %f (\\\"\\n\", 1, 2.5);\n  putint(\"x\");\n  putf(\"%d\\n\", a);\n  return 0;\n}\n";
+  let (ok, stderr) = compile("strings", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: mismatched argument type: expected `int`, found `string`"), "{}", stderr);
+  assert!(stderr.contains("strings.sy:4:10"), "{}", stderr);
+  assert!(stderr.contains("strings.sy:5:16"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 2 previous errors"), "{}", stderr);
+}
+
+#[test]
+fn prototypes_and_extern() {
+  let source = "int odd(int n);\nextern int calls;\nint even(int n) { calls = calls + 1; return n == 0 || odd(n - 1); }\nint odd(int n) { return n != 0 && even(n - 1); }\nint calls;\nint main() { return even(4); }\n";
+  let (ok, stderr) = compile("proto", source);
+  assert!(ok, "{}", stderr);
+
+  let source = "int f(int a);\nfloat f(int a) { return a; }\nextern int g[2];\nint g[3];\nint main() { return 0; }\n";
+  let (ok, stderr) = compile("conflict", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: conflicting types for `f`"), "{}", stderr);
+  assert!(stderr.contains("previously declared here as `int(int)`"), "{}", stderr);
+  assert!(stderr.contains("conflict.sy:4:5"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 2 previous errors"), "{}", stderr);
+}
+
+#[test]
+fn calls_before_definition() {
+  let source = "int main() { return even(4) + g; }\nint g;\nint even(int n) { return n == 0 || odd(n - 1); }\nint odd(int n) { return n != 0 && even(n - 1); }\nint odd(int m) { return 0; }\n";
+  let (ok, stderr) = compile("forward", source);
+  assert!(!ok);
+  assert!(!stderr.contains("undeclared"), "{}", stderr);
+  assert!(stderr.contains("error: ident `odd` defined multiple times"), "{}", stderr);
+  assert!(stderr.contains("forward.sy:5:1"), "{}", stderr);
+  assert!(!stderr.contains("aborting due to"), "{}", stderr);
+
+  // initializers are evaluated in order, only seeing the globals before them
+  let source = "const int a = b + 1;\nconst int b = 1;\nint main() { return a; }\n";
+  let (ok, stderr) = compile("forward_init", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: ident `b` is undeclared"), "{}", stderr);
+}
+
+#[test]
+fn pass_pipeline_dumps() {
+  let source = "int main() {\n  int x = 3, s = 0;\n  while (x > 0) { s = s + x; x = x - 1; }\n  return s;\n}\n";
+  let (ok, stderr) = compile_with("passes", source, &["-passes=mem2reg", "-fdump-before=all", "-fdump-after=mem2reg"]);
+  assert!(ok, "{}", stderr);
+  let (before, after) = stderr.split_once("// ir after mem2reg").unwrap();
+  assert!(before.contains("// ir before mem2reg") && before.contains("alloc i32"), "{}", stderr);
+  assert!(!after.contains("alloc") && after.contains("(%x: i32, %s: i32)"), "{}", stderr);
+
+  let (ok, stderr) = compile_with("passes", source, &["-passes=mem2reg,nothing"]);
+  assert!(!ok);
+  assert!(stderr.contains("[Invalid Args]"), "{}", stderr);
+}
+
+#[test]
+fn control_flow_cleanup() {
+  let source = "int main() {\n  int i = 0;\n  while (1) {\n    if (i > 5) { break; i = 1; }\n    i = i + 1;\n    continue;\n  }\n  if (0) return 1;\n  return i;\n  i = 2;\n}\n";
+  let (ok, stderr) = compile("cfg", source);
+  assert!(ok, "{}", stderr);
+  let dir = std::env::temp_dir().join(format!("compiler-rs-test-{}", std::process::id()));
+  let koopa = fs::read_to_string(dir.join("cfg.koopa")).unwrap();
+  assert!(!koopa.contains("%skipped") && !koopa.contains("%then_0"), "{}", koopa);
+  // no block is left holding a lone jump
+  assert!(!koopa.contains(":\n  jump"), "{}", koopa);
+}
+
+#[test]
+fn constants_through_loops() {
+  let source = "int main() {\n  int x = 3, y = x * 4, i = 0, k = 1;\n  while (i < getint()) {\n    if (k != 1) y = y + 1;\n    i = i + 1;\n  }\n  return y + k;\n}\n";
+  let (ok, stderr) = compile_with("sccp", source, &["-passes=mem2reg,sccp", "-fdump-after=sccp"]);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.contains("ret 13"), "{}", stderr);
+  assert!(!stderr.contains("mul") && !stderr.contains("ne "), "{}", stderr);
+}
+
+#[test]
+fn dead_code_and_stores() {
+  let source = "int main() {\n  int a[4][2], b[3] = {1, 2, 3}, s = 0, i = 0, unused = 0;\n  while (i < 4) {\n    a[i][1] = i * 2;\n    s = s + b[i % 3];\n    unused = unused + s * 3;\n    i = i + 1;\n  }\n  return s;\n}\n";
+  let (ok, stderr) = compile_with("dce", source, &["-passes=mem2reg,dce", "-fdump-after=dce"]);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.contains("%b = alloc") && !stderr.contains("%a = alloc"), "{}", stderr);
+  assert!(!stderr.contains("%unused") && !stderr.contains("mul"), "{}", stderr);
+}
+
+#[test]
+fn errors_point_at_their_nodes() {
+  // an lval, an expression and a function definition
+  let (ok, stderr) = compile("lval_span", "int main() {\n  q = 1;\n  return 0;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: ident `q` is undeclared\n --> "), "{}", stderr);
+  assert!(stderr.contains("lval_span.sy:2:3"), "{}", stderr);
+  let (ok, stderr) = compile("exp_span", "int main() {\n  int a[2];\n  return a + 1;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("exp_span.sy:3:10"), "{}", stderr);
+  let (ok, stderr) = compile("func_span", "int main() {\n  return 0;\n}\nvoid main() {}\n");
+  assert!(!ok);
+  assert!(stderr.contains("func_span.sy:4:1"), "{}", stderr);
+
+  let (ok, stderr) = compile("const_span", "int g = 2;\nconst int d = g;\nint main() {\n  return d;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("const_span.sy:2:15"), "{}", stderr);
+}
+
+#[test]
+fn rendering() {
+  let source = "int x;\nint x;\nint main() {\n  return x;\n}\n";
+  let (ok, stderr) = compile("render", source);
+  assert!(!ok);
+  let dir = std::env::temp_dir().join(format!("compiler-rs-test-{}", std::process::id()));
+  let file = dir.join("render.sy").display().to_string();
+  let expected = format!("\
+error: ident `x` defined multiple times
+ --> {0}:2:5
+  |
+2 | int x;
+  |     ^
+note: previous definition here
+ --> {0}:1:5
+  |
+1 | int x;
+  |     ^
+", file);
+  assert!(stderr.starts_with(&expected), "{}", stderr);
+
+  let (_, stderr) = compile_with("render", source, &["-fdiagnostics-format=json"]);
+  let json = format!(
+    "{{\"level\":\"error\",\"message\":\"ident `x` defined multiple times\",\"file\":{0:?},\"line\":2,\"column\":5,\"end_line\":2,\"end_column\":6,\
+\"notes\":[{{\"message\":\"previous definition here\",\"file\":{0:?},\"line\":1,\"column\":5,\"end_line\":1,\"end_column\":6}}]}}",
+    file,
+  );
+  assert!(stderr.lines().any(|line| line == json), "{}", stderr);
+
+  let (_, stderr) = compile_with("render", source, &["-fdiagnostics-color=always"]);
+  assert!(stderr.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: ident `x` defined multiple times\x1b[0m"), "{}", stderr);
+}
+
+#[test]
+fn error_limit() {
+  let source = "int main() {\n  a = 1;\n  b = 2;\n  c = 3;\n  return 0;\n}\n";
+  let (ok, stderr) = compile("limit", source);
+  assert!(!ok);
+  assert!(stderr.contains("aborting due to 3 previous errors"), "{}", stderr);
+
+  let (ok, stderr) = compile_with("limit", source, &["-ferror-limit=2"]);
+  assert!(!ok);
+  assert_eq!(stderr.matches("is undeclared").count(), 2, "{}", stderr);
+  assert!(!stderr.contains("`c`"), "{}", stderr);
+  assert!(stderr.contains("error: too many errors emitted, stopping now"), "{}", stderr);
+
+  let (_, stderr) = compile_with("limit", source, &["-ferror-limit=0"]);
+  assert_eq!(stderr.matches("is undeclared").count(), 3, "{}", stderr);
+  assert!(!stderr.contains("too many errors"), "{}", stderr);
+}
+
+#[test]
+fn parser_recovery() {
+  let source = "int main() {\n  int a = 1 +;\n  if (a { a = 2; }\n  while () ;\n  return a a;\n}\n";
+  let (ok, stderr) = compile("recover", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: expected expression, found `;`"), "{}", stderr);
+  assert!(stderr.contains("recover.sy:2:14"), "{}", stderr);
+  assert!(stderr.contains("error: expected `)` after expression, found `{`"), "{}", stderr);
+  assert!(stderr.contains("recover.sy:3:9"), "{}", stderr);
+  assert!(stderr.contains("error: expected expression, found `)`"), "{}", stderr);
+  assert!(stderr.contains("recover.sy:4:10"), "{}", stderr);
+  assert!(stderr.contains("error: expected `;` after expression, found `a`"), "{}", stderr);
+  assert!(stderr.contains("recover.sy:5:12"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 4 previous errors"), "{}", stderr);
+
+  // a missing `;` is reported at the next token
+  let (ok, stderr) = compile("semi", "int main() {\n  int b = 2\n  return b;\n}\n");
+  assert!(!ok);
+  assert!(stderr.contains("error: expected `;` after expression, found `return`"), "{}", stderr);
+  assert!(stderr.contains("semi.sy:3:3"), "{}", stderr);
+
+  // what is expected comes from the parser state, not the brackets around
+  let source = "int f(int a int b) { return a; }\nint main() {\n  int c = 1 ? 2 3;\n  return c[0;\n}\n";
+  let (ok, stderr) = compile("expected", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: expected one of `)`, `,`, `[`, found `int`"), "{}", stderr);
+  assert!(stderr.contains("expected.sy:1:13"), "{}", stderr);
+  assert!(stderr.contains("error: expected `:` after expression, found `3`"), "{}", stderr);
+  assert!(stderr.contains("expected.sy:3:17"), "{}", stderr);
+  assert!(stderr.contains("error: expected `]` after expression, found `;`"), "{}", stderr);
+  assert!(stderr.contains("expected.sy:4:13"), "{}", stderr);
+}
+
+#[test]
+fn semantic_errors() {
+  let source = "const int c = 1;\nint g = 2;\nconst int d = g;\nint a[2] = {1, {2}, 3};\nint main() {\n  int s;\n  c = 2;\n  s[0] = 1;\n  int b[2];\n  b = 1;\n  return b[0][1] + h();\n}\n";
+  let (ok, stderr) = compile("sema", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: failed in eval const expr\n --> "), "{}", stderr);
+  assert!(stderr.contains("sema.sy:3:15"), "{}", stderr);
+  assert!(stderr.contains("error: invalid initializer detected"), "{}", stderr);
+  assert!(stderr.contains("sema.sy:4:5"), "{}", stderr);
+  // assigning to a constant, indexing a scalar, assigning to an array, too many indices
+  assert_eq!(stderr.matches("error: invalid value type detected").count(), 4, "{}", stderr);
+  for pos in ["7:3", "8:3", "10:3", "11:10"] {
+    assert!(stderr.contains(&format!("sema.sy:{}", pos)), "{}", stderr);
+  }
+  assert!(stderr.contains("error: ident `h` is undeclared"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 7 previous errors"), "{}", stderr);
+  let dir = std::env::temp_dir().join(format!("compiler-rs-test-{}", std::process::id()));
+  assert!(!dir.join("sema.koopa").exists());
+}
+
+#[test]
+fn call_arity_and_argument_types() {
+  let source = "int f(int a[][3]) { return a[0][0]; }\nint main() {\n  int x[2][4], y;\n  putint(1, 2);\n  f(y);\n  f(x);\n  f(x[0]);\n  return f();\n}\n";
+  let (ok, stderr) = compile("arity", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: function `putint` takes 1 argument but 2 were supplied"), "{}", stderr);
+  assert!(stderr.contains("arity.sy:4:3"), "{}", stderr);
+  assert!(stderr.contains("error: mismatched argument type: expected `int[][3]`, found `int`\n --> "), "{}", stderr);
+  assert!(stderr.contains("arity.sy:5:5"), "{}", stderr);
+  assert!(stderr.contains("error: mismatched argument type: expected `int[][3]`, found `int[][4]`"), "{}", stderr);
+  assert!(stderr.contains("arity.sy:6:5"), "{}", stderr);
+  assert!(stderr.contains("error: mismatched argument type: expected `int[][3]`, found `int[]`"), "{}", stderr);
+  assert!(stderr.contains("arity.sy:7:5"), "{}", stderr);
+  assert!(stderr.contains("error: function `f` takes 1 argument but 0 were supplied"), "{}", stderr);
+  assert!(stderr.contains("note: `f` defined here"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 5 previous errors"), "{}", stderr);
+
+  let source = "int f(int a[][3]) { return a[1][2]; }\nint main() {\n  int x[2][3], y[3];\n  getarray(y);\n  return f(x) + getarray(x[1]);\n}\n";
+  let (ok, stderr) = compile("arity_ok", source);
+  assert!(ok, "{}", stderr);
+}
+
+#[test]
+fn return_and_void_values() {
+  let source = "void f() { return 1; }\nint g() { return; }\nint h(int x) { if (x) return 1; }\nint main() {\n  int v = f();\n  return g() + h(v);\n}\n";
+  let (ok, stderr) = compile("ret", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: void function `f` should not return a value"), "{}", stderr);
+  assert!(stderr.contains("ret.sy:1:12"), "{}", stderr);
+  assert!(stderr.contains("error: non-void function `g` should return a value"), "{}", stderr);
+  assert!(stderr.contains("ret.sy:2:11"), "{}", stderr);
+  assert!(stderr.contains("warning: non-void function `h` does not return a value in all control paths [-Wreturn-type]"), "{}", stderr);
+  assert!(stderr.contains("ret.sy:3:1"), "{}", stderr);
+  assert!(stderr.contains("error: void value used in an expression"), "{}", stderr);
+  assert!(stderr.contains("ret.sy:5:11"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 3 previous errors"), "{}", stderr);
+
+  // returning on every path, or through a loop that never ends
+  let source = "int h(int x) { if (x) return 1; else return 2; }\nint k() { while (1) { } }\nvoid v() { }\nint main() {\n  v();\n  return h(1) + k();\n}\n";
+  let (ok, stderr) = compile("ret_ok", source);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.is_empty(), "{}", stderr);
+
+  // or being main, which returns 0 at its end
+  let (ok, stderr) = compile_with("ret_main", "int main() {\n  putint(1);\n}\n", &["-Werror"]);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.is_empty(), "{}", stderr);
+}
+
+#[test]
+fn constant_conditions_only_with_wall() {
+  let source = "int main() {\n  const int n = 2;\n  if (0) return 1;\n  if (n > 1) return 2;\n  return 0;\n}\n";
+  let (ok, stderr) = compile("constcond", source);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.is_empty(), "{}", stderr);
+
+  let (ok, stderr) = compile_with("constcond", source, &["-Wall"]);
+  assert!(ok, "{}", stderr);
+  assert!(stderr.contains("warning: condition is always constant [-Wconstant-condition]"), "{}", stderr);
+  assert!(stderr.contains("constcond.sy:4:7"), "{}", stderr);
+}
+
+#[test]
+fn intrinsic_names_are_reserved() {
+  let source = "int __fadd(int a, int b) { return a - b; }\nint __itof(int a);\nint __dlo;\nint main() {\n  int __fsub = 1;\n  return __fadd(__fsub, 2);\n}\n";
+  let (ok, stderr) = compile("reserved", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: ident `__fadd` is reserved for the compiler"), "{}", stderr);
+  assert!(stderr.contains("reserved.sy:1:1"), "{}", stderr);
+  assert!(stderr.contains("error: ident `__itof` is reserved for the compiler"), "{}", stderr);
+  assert!(stderr.contains("error: ident `__dlo` is reserved for the compiler"), "{}", stderr);
+  // a local takes no global name
+  assert!(!stderr.contains("`__fsub`"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 3 previous errors"), "{}", stderr);
+}
+
+#[test]
+fn library_prototypes() {
+  let source = "int getint();\nvoid putint(int n);\nint getarray(int a[]);\nint main() {\n  int a[2];\n  putint(getint() + getarray(a));\n  return 0;\n}\n";
+  let (ok, stderr) = compile("libproto", source);
+  assert!(ok, "{}", stderr);
+
+  let source = "float getint();\nvoid putch(int c) { }\nint main() {\n  return 0;\n}\n";
+  let (ok, stderr) = compile("libconflict", source);
+  assert!(!ok);
+  assert!(stderr.contains("error: conflicting types for `getint`"), "{}", stderr);
+  assert!(stderr.contains("libconflict.sy:1:1"), "{}", stderr);
+  assert!(stderr.contains("note: `getint` is provided by the runtime library as `int()`"), "{}", stderr);
+  assert!(stderr.contains("error: ident `putch` defined multiple times"), "{}", stderr);
+  assert!(stderr.contains("libconflict.sy:2:1"), "{}", stderr);
+  assert!(stderr.contains("aborting due to 2 previous errors"), "{}", stderr);
+}
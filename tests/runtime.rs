@@ -0,0 +1,606 @@
+// runtime tests, compiling programs to risc-v and running them in a simulator
+
+mod sim;
+
+use std::fs;
+use std::process::Command;
+
+// compile [source] to assembly in [mode], -riscv or -perf
+fn compile(name: &str, source: &str, mode: &str, flags: &[&str]) -> String {
+  let dir = std::env::temp_dir().join(format!("compiler-rs-run-{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let input = dir.join(format!("{}.sy", name));
+  let output = dir.join(format!("{}{}{}.s", name, mode, flags.concat()));
+  fs::write(&input, source).unwrap();
+  let result = Command::new(env!("CARGO_BIN_EXE_compiler-rs"))
+    .arg(mode)
+    .arg(&input)
+    .arg("-o")
+    .arg(&output)
+    .arg("-fdiagnostics-color=never")
+    .args(flags)
+    .output()
+    .unwrap();
+  assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+  fs::read_to_string(output).unwrap()
+}
+
+// run [source] built both without and with optimizations, which must agree,
+// giving the output and exit code
+fn run(name: &str, source: &str, input: &str) -> (String, i32) {
+  run_with(name, source, input, &[])
+}
+
+// as [run], agreeing as well with builds running only the [passes] given
+fn run_with(name: &str, source: &str, input: &str, passes: &[&str]) -> (String, i32) {
+  let plain = sim::run(&compile(name, source, "-riscv", &[]), input).unwrap();
+  let optimized = sim::run(&compile(name, source, "-perf", &[]), input).unwrap();
+  assert_eq!(plain, optimized);
+  for pass in passes {
+    let flag = format!("-passes={}", pass);
+    let result = sim::run(&compile(name, source, "-riscv", &[&flag]), input).unwrap();
+    assert_eq!(plain, result, "{}", flag);
+  }
+  plain
+}
+
+#[test]
+fn optimized_matches_plain() {
+  let source = "\
+int fib(int n) {
+  int a = 0, b = 1, i = 0;
+  while (i < n) {
+    int t = a + b;
+    a = b;
+    b = t;
+    i = i + 1;
+  }
+  return a;
+}
+int main() {
+  int n = getint(), sum = 0, i = 0;
+  while (i < n) {
+    if (i % 3 == 0) sum = sum + fib(i);
+    else sum = sum - i;
+    i = i + 1;
+  }
+  putint(sum);
+  putch(10);
+  return sum % 256;
+}
+";
+  let (output, code) = run("fib", source, "12");
+  assert_eq!(output, "-4\n");
+  assert_eq!(code, 252);
+}
+
+#[test]
+fn long_dependency_chains() {
+  // each value used twice by the next, a dag whose paths double at every step
+  let mut source = String::from("int main() {\n  int x = getint();\n");
+  for _ in 0..64 {
+    source += "  x = x * x - x + x;\n";
+  }
+  source += "  putint(x);\n  return 0;\n}\n";
+  let (output, _) = run("chain", &source, "1");
+  assert_eq!(output, "1");
+}
+
+#[test]
+fn int_min_literal() {
+  let source = "\
+const int m = -2147483648;
+int g[2] = {-2147483648, 1};
+int main() {
+  int a = -2147483648, b = getint();
+  putint(a);
+  putch(32);
+  putint(g[0] == m);
+  putch(32);
+  putint(-2147483648 + b);
+  return 0;
+}
+";
+  let (output, _) = run("int_min", source, "5");
+  assert_eq!(output, "-2147483648 1 -2147483643");
+}
+
+#[test]
+fn switch_fall_through() {
+  let source = "\
+int classify(int x) {
+  int r = 0;
+  switch (x) {
+    case 0: r = r + 1;
+    default: r = r + 10;
+    int y;
+    case 1: y = 100; r = r + y;
+      break;
+    case 2: r = r + 1000;
+  }
+  return r;
+}
+int main() {
+  putint(classify(0)); putch(32);
+  putint(classify(1)); putch(32);
+  putint(classify(2)); putch(32);
+  putint(classify(7)); putch(10);
+  // the declaration is jumped over, its storage is still there
+  int x = getint();
+  switch (x) { default: break; int y; case 1: y = 3; putint(y); }
+  return 0;
+}
+";
+  let (output, _) = run("fallthrough", source, "1");
+  assert_eq!(output, "111 100 1000 110\n3");
+  let (output, _) = run("fallthrough", source, "2");
+  assert_eq!(output, "111 100 1000 110\n");
+}
+
+#[test]
+fn declaration_jumped_over_by_a_case() {
+  // [y] is declared where no edge reaches, and used by the case after it
+  let source = "\
+int main() {
+  int x = getint();
+  switch (x) { default: break; int y; case 1: y = 3; putint(y); }
+  return 0;
+}
+";
+  let passes = ["mem2reg", "sccp", "dce", "simplifycfg", "mem2reg,sccp", "mem2reg,sccp,dce,simplifycfg"];
+  let (output, _) = run_with("jumped_decl", source, "1", &passes);
+  assert_eq!(output, "3");
+  let (output, _) = run_with("jumped_decl", source, "0", &passes);
+  assert_eq!(output, "");
+}
+
+#[test]
+fn compound_assignment_evaluates_its_target_once() {
+  let source = "\
+int calls;
+int next() {
+  calls = calls + 1;
+  return calls;
+}
+int main() {
+  int a[4] = {10, 20, 30, 40};
+  a[next()] += 1;
+  a[next()] *= 3;
+  a[next()]++;
+  putint(calls); putch(32);
+  putint(a[0]); putch(32);
+  putint(a[1]); putch(32);
+  putint(a[2]); putch(32);
+  putint(a[3]);
+  return 0;
+}
+";
+  let (output, _) = run("lvalue_once", source, "");
+  assert_eq!(output, "3 10 21 90 41");
+}
+
+// [op]= applied to a scalar and an array element, read from the input
+fn compound_assign(name: &str, op: &str, input: &str) -> String {
+  let source = format!("\
+int main() {{
+  int x = getint(), y = getint(), a[2] = {{0, x}};
+  x {op}= y;
+  a[1] {op}= y;
+  putint(x); putch(32);
+  putint(a[1]);
+  return 0;
+}}
+");
+  run(name, &source, input).0
+}
+
+#[test]
+fn remainder_assignment() {
+  assert_eq!(compound_assign("rem_assign", "%", "-7 3"), "-1 -1");
+  assert_eq!(compound_assign("rem_assign", "%", "7 -3"), "1 1");
+}
+
+#[test]
+fn shift_left_assignment() {
+  assert_eq!(compound_assign("shl_assign", "<<", "3 4"), "48 48");
+  assert_eq!(compound_assign("shl_assign", "<<", "-1 31"), "-2147483648 -2147483648");
+}
+
+#[test]
+fn shift_right_assignment() {
+  assert_eq!(compound_assign("shr_assign", ">>", "100 3"), "12 12");
+  assert_eq!(compound_assign("shr_assign", ">>", "-16 2"), "-4 -4");
+}
+
+#[test]
+fn and_assignment() {
+  assert_eq!(compound_assign("and_assign", "&", "12 10"), "8 8");
+  assert_eq!(compound_assign("and_assign", "&", "-1 255"), "255 255");
+}
+
+#[test]
+fn or_assignment() {
+  assert_eq!(compound_assign("or_assign", "|", "12 10"), "14 14");
+  assert_eq!(compound_assign("or_assign", "|", "-256 1"), "-255 -255");
+}
+
+#[test]
+fn xor_assignment() {
+  assert_eq!(compound_assign("xor_assign", "^", "12 10"), "6 6");
+  assert_eq!(compound_assign("xor_assign", "^", "-1 5"), "-6 -6");
+}
+
+#[test]
+fn conditional_operator_runs_one_branch() {
+  let source = "\
+int calls;
+int count(int v) {
+  calls = calls + 1;
+  return v;
+}
+int sign(int x) {
+  return x > 0 ? 1 : x < 0 ? -1 : 0;
+}
+int main() {
+  int x = getint();
+  int a = x ? count(10) : count(20);
+  int b = !x ? count(30) : count(40);
+  putint(a); putch(32);
+  putint(b); putch(32);
+  putint(calls); putch(32);
+  putint(sign(x)); putch(32);
+  putint(sign(-x)); putch(32);
+  putint(sign(0)); putch(32);
+  // nested in the condition and both branches
+  putint((x > 2 ? x < 5 : x > -3) ? (x ? 7 : 8) : (x ? 9 : 10));
+  return 0;
+}
+";
+  let (output, _) = run("ternary", source, "3");
+  assert_eq!(output, "10 40 2 1 -1 0 7");
+  let (output, _) = run("ternary", source, "0");
+  assert_eq!(output, "20 30 2 0 0 0 8");
+  let (output, _) = run("ternary", source, "-4");
+  assert_eq!(output, "10 40 2 -1 1 0 9");
+}
+
+#[test]
+fn hex_float_literals_round_once() {
+  let source = "\
+int main() {
+  // only past the bits of a double is it above halfway to the next float
+  putint(0x1.0000010000000001p0 > 1.0); putch(32);
+  // ties go to the even one
+  putint(0x1.000001p0 == 1.0); putch(32);
+  putint(0x1.000003p0 == 0x1.000004p0); putch(32);
+  putint(0x1p-150 == 0.0); putch(32);
+  putint(0x1.0000000001p-150 == 0x1p-149); putch(32);
+  putint(0x1.ffffffp127 > 0x1.fffffep127);
+  return 0;
+}
+";
+  let (output, _) = run("hex_float", source, "");
+  assert_eq!(output, "1 1 1 1 1 1");
+}
+
+#[test]
+fn float_arithmetic() {
+  let source = "\
+float half(float x) {
+  return x / 2;
+}
+int main() {
+  float a = getfloat(), b = getfloat();
+  putfloat(a + b); putch(32);
+  putfloat(a - b); putch(32);
+  putfloat(a * b); putch(32);
+  putfloat(a / b); putch(32);
+  putfloat(-a); putch(10);
+  putint(a < b); putint(a > b); putint(a == b);
+  putint(a != b); putint(a <= b); putint(a >= b); putint(!b); putch(10);
+  putfloat(half(a));
+  return 0;
+}
+";
+  let (output, _) = run("float_ops", source, "1.5 0x1p-2");
+  assert_eq!(output, "0x1.cp+0 0x1.4p+0 0x1.8p-2 0x1.8p+2 -0x1.8p+0\n0101010\n0x1.8p-1");
+}
+
+#[test]
+fn int_float_conversions() {
+  let source = "\
+int truncate(float x) {
+  return x;
+}
+float widen(int x) {
+  return x;
+}
+int main() {
+  int i = getint();
+  float f = i;
+  putint(truncate(2.9)); putch(32);
+  putint(truncate(-2.9)); putch(32);
+  putfloat(f / 4); putch(32);
+  // the nearest float, the even one at a tie
+  putint(widen(16777217) == 16777216.0); putch(32);
+  int k = 3.99 + i;
+  putint(k); putch(32);
+  i = i / 2.0;
+  putint(i); putch(32);
+  putint(i * 1.5 == 4.5);
+  return 0;
+}
+";
+  let (output, _) = run("float_conv", source, "7");
+  assert_eq!(output, "2 -2 0x1.cp+0 1 10 3 1");
+}
+
+#[test]
+fn hex_float_literals() {
+  let source = "\
+const float third = 0x1.555556p-2;
+int main() {
+  putfloat(0x1.8p1 + 0x.8p0); putch(32);
+  putfloat(0XAp-1); putch(32);
+  putfloat(0x1P+4); putch(32);
+  putfloat(third * 3); putch(32);
+  putfloat(0x1p-149);
+  return 0;
+}
+";
+  let (output, _) = run("hex_floats", source, "");
+  assert_eq!(output, "0x1.cp+1 0x1.4p+2 0x1p+4 0x1p+0 0x1p-149");
+}
+
+#[test]
+fn float_arrays() {
+  let source = "\
+float g[3] = {0.5, 1};
+float sum(float a[], int n) {
+  float s = 0;
+  int i = 0;
+  while (i < n) {
+    s = s + a[i];
+    i = i + 1;
+  }
+  return s;
+}
+int main() {
+  float a[4];
+  int n = getfarray(a);
+  putfarray(n, a);
+  putfloat(sum(a, n)); putch(10);
+  float b[2][2] = {{1.5, 2}, {3}};
+  b[1][1] = b[0][0] * g[1];
+  putfarray(4, b[0]);
+  putfarray(3, g);
+  return 0;
+}
+";
+  let (output, _) = run("float_arrays", source, "3 1.5 -2 0x1p3");
+  assert_eq!(
+    output,
+    "3: 0x1.8p+0 -0x1p+1 0x1p+3\n0x1.ep+2\n4: 0x1.8p+0 0x1p+1 0x1.8p+1 0x1.8p+0\n3: 0x1p-1 0x1p+0 0x0p+0\n"
+  );
+}
+
+#[test]
+fn float_args_and_returns() {
+  // more float args than float registers, mixed with ints
+  let source = "\
+float mix(float a, int b, float c, int d, float e, float f, float g, float h,
+          float i, float j, float k, int l, float m) {
+  return a + b * c - d + e * f + g - h + i * j - k + l + m;
+}
+float power(float x, int n) {
+  if (n == 0) return 1;
+  return x * power(x, n - 1);
+}
+int main() {
+  putfloat(mix(1.5, 2, 0.25, 3, 0.5, 4, 1.25, 0.75, 2, 1.5, 0.125, 5, 0.375)); putch(32);
+  putfloat(power(getfloat(), getint()));
+  return 0;
+}
+";
+  let (output, _) = run("float_args", source, "1.5 3");
+  assert_eq!(output, "0x1.38p+3 0x1.bp+1");
+}
+
+#[test]
+fn float_constants_fold_as_computed() {
+  // folded by sccp, the same bits as the f extension gives, nans and saturation included
+  let source = "\
+int main() {
+  float zero = 0, big = 1e10, x = 2.5;
+  float nan = zero / zero;
+  putfloat(nan); putch(32);
+  putfloat(-nan); putch(32);
+  putint(nan); putch(32);
+  putint(big); putch(32);
+  putint(-big); putch(32);
+  putint(nan == nan); putint(nan != nan); putint(nan < x); putint(x >= -big); putch(32);
+  putfloat(1 / zero); putch(32);
+  putfloat(x * 3 - 0.5); putch(32);
+  putint(16777217); putch(32);
+  putfloat(16777217); putch(32);
+  putf(\"%f\", x / 4);
+  return 0;
+}
+";
+  let (output, _) = run_with("float_fold", source, "", &["sccp", "mem2reg,sccp,dce"]);
+  assert_eq!(
+    output,
+    "nan -nan 2147483647 2147483647 -2147483648 0101 inf 0x1.cp+2 16777217 0x1p+24 0.625000"
+  );
+}
+
+#[test]
+fn variadic_floats() {
+  // each promoted to double in an aligned pair of words, the last ones on the stack
+  let source = "\
+int main() {
+  float x = getfloat();
+  putf(\"%f|%d %f|%d %d %f %f %c\\n\", x, 7, -x / 3, 1, 2, x * x, 0.1, 65);
+  putf(\"%d %f %f %f %f %f\\n\", 9, 1.5, x, 1e10, -0.0, 1 / 3.0);
+  return 0;
+}
+";
+  let (output, _) = run("varfloat", source, "2.5");
+  assert_eq!(
+    output,
+    "2.500000|7 -0.833333|1 2 6.250000 0.100000 A\n9 1.500000 2.500000 10000000000.000000 -0.000000 0.333333\n"
+  );
+}
+
+#[test]
+fn mutual_recursion() {
+  // functions and globals used before their definitions
+  let source = "\
+int is_even(int n) {
+  calls = calls + 1;
+  if (n == 0) return 1;
+  return is_odd(n - 1);
+}
+int is_odd(int n) {
+  calls = calls + 1;
+  if (n == 0) return 0;
+  return is_even(n - 1);
+}
+int main() {
+  int n = getint();
+  putint(is_even(n)); putch(32);
+  putint(is_odd(n)); putch(32);
+  putint(calls); putch(32);
+  putint(limit[1]);
+  return 0;
+}
+int calls;
+int limit[2] = {7, 9};
+";
+  let (output, _) = run("mutual", source, "5");
+  assert_eq!(output, "0 1 12 9");
+  let (output, _) = run("mutual", source, "8");
+  assert_eq!(output, "1 0 18 9");
+}
+
+#[test]
+fn globals_named_like_string_literals() {
+  let source = "\
+int __str_0[2] = {1, 2};
+int main() {
+  __str_0[0] = 5;
+  putf(\"%d %d\\n\", __str_0[0], __str_0[1]);
+  putf(\"ok\\n\");
+  return 0;
+}
+";
+  let (output, _) = run("str_names", source, "");
+  assert_eq!(output, "5 2\nok\n");
+}
+
+#[test]
+fn promoted_swaps() {
+  // values rotating through block params, each read after the others are written
+  let source = "\
+int main() {
+  int a = 1, b = 2, c = 3, i = 0, n = getint();
+  while (i < n) {
+    int t = a;
+    a = b;
+    b = c;
+    c = t;
+    if (a > b) {
+      t = a;
+      a = b;
+      b = t;
+    }
+    i = i + 1;
+  }
+  putint(a * 100 + b * 10 + c);
+  return 0;
+}
+";
+  for (input, expected) in [("0", "123"), ("1", "231"), ("2", "132"), ("5", "231")] {
+    let (output, _) = run_with("swaps", source, input, &["mem2reg"]);
+    assert_eq!(output, expected, "{}", input);
+  }
+}
+
+#[test]
+fn promoted_across_break_and_continue() {
+  let source = "\
+int main() {
+  int i = 0, s = 0, last = -1;
+  while (1) {
+    i = i + 1;
+    if (i % 2 == 0) continue;
+    if (i > 9) break;
+    int j = 0;
+    while (j < i) {
+      j = j + 1;
+      if (j == 3) continue;
+      s = s + j;
+      last = j;
+      if (s > 40) break;
+    }
+  }
+  putint(s);
+  putch(32);
+  putint(last);
+  putch(32);
+  putint(i);
+  return 0;
+}
+";
+  let (output, _) = run_with("loops", source, "", &["mem2reg", "mem2reg,simplifycfg"]);
+  assert_eq!(output, "42 1 11");
+}
+
+#[test]
+fn promoted_reads_before_stores() {
+  // [u] is only read once a previous iteration stored it, [v] is read in the iteration it is stored
+  let source = "\
+int main() {
+  int i = 0, u, v;
+  while (i < 3) {
+    if (i > 0) putint(u);
+    if (i == 1) v = 7;
+    if (i >= 1) putint(v);
+    u = i * 5;
+    i = i + 1;
+  }
+  return u;
+}
+";
+  let (output, code) = run_with("undef", source, "", &["mem2reg"]);
+  assert_eq!(output, "0757");
+  assert_eq!(code, 10);
+}
+
+#[test]
+fn branch_on_read_before_store() {
+  // [x] and [u] are never stored, sccp takes the branches on them as on zero
+  let source = "\
+int first(int n) {
+  int u, i = 0;
+  while (i < n) {
+    if (u) return i;
+    i = i + 1;
+  }
+  return -1;
+}
+int main() {
+  int i = 0, x, s = 0;
+  while (i < 3) {
+    if (x) s = s + 10;
+    else s = s + 1;
+    i = i + 1;
+  }
+  putint(first(4)); putch(32);
+  putint(s);
+  return 0;
+}
+";
+  let (output, _) = run_with("undef_branch", source, "", &["mem2reg,sccp", "mem2reg,sccp,dce,simplifycfg"]);
+  assert_eq!(output, "-1 3");
+}
@@ -0,0 +1,459 @@
+/*
+  a small rv32imf simulator for the assembly the compiler emits, to run compiled programs in tests:
+  - the sysy runtime functions are built in, reading input from a string
+  - .rodata is read-only, a store to it fails the run as it faults on a board
+  - registers a call may clobber are scrambled after each runtime function, float ones included
+*/
+
+use std::collections::HashMap;
+
+const MEM_SIZE: usize = 1 << 24;
+const RODATA_BASE: usize = 0x1000;
+const DATA_BASE: usize = 0x10_0000;
+const STACK_TOP: usize = MEM_SIZE - 16;
+const EXIT: i32 = -1; // return address of main
+const STEP_LIMIT: u64 = 50_000_000;
+
+const REGS: [&str; 32] = [
+  "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1",
+  "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+  "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+const FREGS: [&str; 32] = [
+  "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1",
+  "fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7",
+  "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+const CLOBBERED: [&str; 14] = ["t0", "t1", "t2", "t3", "t4", "t5", "t6", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+const FCLOBBERED: [&str; 19] = [
+  "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "ft8", "ft9", "ft10", "ft11",
+  "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7",
+];
+const GARBAGE: i32 = 0x5a5a_5a5a;
+
+#[derive(Clone, Copy)]
+enum Label {
+  Text(usize), // index of an instruction
+  Data(usize), // address
+}
+
+struct Sim {
+  mem: Vec<u8>,
+  x: [i32; 32],
+  f: [f32; 32],
+  text: Vec<(String, Vec<String>)>,
+  labels: HashMap<String, Label>,
+  rodata_end: usize,
+  input: Vec<u8>,
+  pos: usize,
+  output: String,
+}
+
+// run the program from `main`, giving its output and exit code
+pub fn run(asm: &str, input: &str) -> Result<(String, i32), String> {
+  let mut sim = Sim {
+    mem: vec![0; MEM_SIZE],
+    x: [0; 32],
+    f: [0.0; 32],
+    text: Vec::new(),
+    labels: HashMap::new(),
+    rodata_end: RODATA_BASE,
+    input: input.as_bytes().to_vec(),
+    pos: 0,
+    output: String::new(),
+  };
+  sim.load(asm)?;
+  let code = sim.run()?;
+  Ok((sim.output, code))
+}
+
+fn reg(name: &str) -> Result<usize, String> {
+  REGS.iter().position(|r| *r == name).ok_or(format!("bad register `{}`", name))
+}
+
+fn freg(name: &str) -> Result<usize, String> {
+  FREGS.iter().position(|r| *r == name).ok_or(format!("bad float register `{}`", name))
+}
+
+fn imm(s: &str) -> Result<i32, String> {
+  s.parse().map_err(|_| format!("bad immediate `{}`", s))
+}
+
+impl Sim {
+  fn load(&mut self, asm: &str) -> Result<(), String> {
+    let (mut rodata, mut data) = (RODATA_BASE, DATA_BASE);
+    let mut in_rodata = None; // [None] in .text
+    let mut pending = Vec::new(); // words holding the address of a label
+    for line in asm.lines() {
+      let line = line.split('#').next().unwrap().trim();
+      if line.is_empty() {
+        continue;
+      }
+      let ptr = match in_rodata {
+        Some(true) => &mut rodata,
+        _ => &mut data,
+      };
+      if let Some(name) = line.strip_suffix(':') {
+        let label = match in_rodata {
+          None => Label::Text(self.text.len()),
+          Some(_) => Label::Data(*ptr),
+        };
+        self.labels.insert(name.to_string(), label);
+        continue;
+      }
+      let (op, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+      let rest = rest.trim();
+      match op {
+        ".text" => in_rodata = None,
+        ".data" => in_rodata = Some(false),
+        ".section" => in_rodata = Some(rest == ".rodata"),
+        ".globl" | ".align" | ".p2align" => {},
+        ".word" => {
+          for word in rest.split(',').map(str::trim) {
+            match word.parse::<i32>() {
+              Ok(v) => self.mem[*ptr..*ptr + 4].copy_from_slice(&v.to_le_bytes()),
+              Err(_) => pending.push((*ptr, word.to_string())),
+            }
+            *ptr += 4;
+          }
+        },
+        ".zero" => *ptr += imm(rest)? as usize,
+        _ if op.starts_with('.') => return Err(format!("unknown directive `{}`", op)),
+        _ => {
+          let args = if rest.is_empty() { vec![] } else { rest.split(',').map(|a| a.trim().to_string()).collect() };
+          self.text.push((op.to_string(), args));
+        },
+      }
+    }
+    self.rodata_end = rodata;
+    for (addr, name) in pending {
+      let value = self.data_label(&name)? as i32;
+      self.mem[addr..addr + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(())
+  }
+
+  fn data_label(&self, name: &str) -> Result<usize, String> {
+    match self.labels.get(name) {
+      Some(Label::Data(addr)) => Ok(*addr),
+      _ => Err(format!("no data label `{}`", name)),
+    }
+  }
+
+  fn text_label(&self, name: &str) -> Result<usize, String> {
+    match self.labels.get(name) {
+      Some(Label::Text(idx)) => Ok(*idx),
+      _ => Err(format!("no text label `{}`", name)),
+    }
+  }
+
+  fn get(&self, name: &str) -> Result<i32, String> {
+    Ok(self.x[reg(name)?])
+  }
+
+  fn set(&mut self, name: &str, value: i32) -> Result<(), String> {
+    let r = reg(name)?;
+    if r != 0 {
+      self.x[r] = value;
+    }
+    Ok(())
+  }
+
+  // address of an `offset(base)` operand
+  fn addr(&self, operand: &str) -> Result<usize, String> {
+    let (offset, base) = operand.strip_suffix(')').and_then(|o| o.split_once('('))
+      .ok_or(format!("bad address `{}`", operand))?;
+    Ok(self.get(base)?.wrapping_add(imm(offset)?) as u32 as usize)
+  }
+
+  fn load_word(&self, addr: usize) -> Result<i32, String> {
+    if !(RODATA_BASE..MEM_SIZE - 4).contains(&addr) || !addr.is_multiple_of(4) {
+      return Err(format!("bad load from {:#x}", addr));
+    }
+    Ok(i32::from_le_bytes(self.mem[addr..addr + 4].try_into().unwrap()))
+  }
+
+  fn store_word(&mut self, addr: usize, value: i32) -> Result<(), String> {
+    if !(RODATA_BASE..MEM_SIZE - 4).contains(&addr) || !addr.is_multiple_of(4) {
+      return Err(format!("bad store to {:#x}", addr));
+    }
+    if addr < self.rodata_end {
+      return Err(format!("store to read-only data at {:#x}", addr));
+    }
+    self.mem[addr..addr + 4].copy_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn run(&mut self) -> Result<i32, String> {
+    self.set("sp", STACK_TOP as i32)?;
+    self.set("ra", EXIT)?;
+    let mut pc = self.text_label("main")?;
+    for _ in 0..STEP_LIMIT {
+      let (op, a) = self.text.get(pc).cloned().ok_or(format!("pc out of text: {}", pc))?;
+      pc += 1;
+      match op.as_str() {
+        "li" => self.set(&a[0], imm(&a[1])?)?,
+        "la" => self.set(&a[0], self.data_label(&a[1])? as i32)?,
+        "mv" => self.set(&a[0], self.get(&a[1])?)?,
+        "seqz" => self.set(&a[0], (self.get(&a[1])? == 0) as i32)?,
+        "snez" => self.set(&a[0], (self.get(&a[1])? != 0) as i32)?,
+        "lw" => self.set(&a[0], self.load_word(self.addr(&a[1])?)?)?,
+        "sw" => self.store_word(self.addr(&a[1])?, self.get(&a[0])?)?,
+        "beqz" | "bnez" => if (self.get(&a[0])? == 0) == (op == "beqz") {
+          pc = self.text_label(&a[1])?;
+        },
+        "j" => pc = self.text_label(&a[0])?,
+        "call" => match self.labels.get(&a[0]).copied() {
+          Some(Label::Text(idx)) => {
+            self.set("ra", pc as i32)?;
+            pc = idx;
+          },
+          _ => self.runtime(&a[0])?,
+        },
+        "ret" => match self.get("ra")? {
+          EXIT => return Ok(self.get("a0")? & 0xff),
+          ra => pc = ra as usize,
+        },
+        "addi" | "andi" | "ori" | "xori" | "slli" | "srli" | "srai" | "slti" => {
+          let value = alu(&op[..op.len() - 1], self.get(&a[1])?, imm(&a[2])?)?;
+          self.set(&a[0], value)?;
+        },
+        "fmv.w.x" => self.f[freg(&a[0])?] = f32::from_bits(self.get(&a[1])? as u32),
+        "fmv.x.w" => self.set(&a[0], self.f[freg(&a[1])?].to_bits() as i32)?,
+        "fcvt.s.w" => self.f[freg(&a[0])?] = self.get(&a[1])? as f32,
+        // saturating and truncated, as rtz asks, a nan gives the largest int
+        "fcvt.w.s" => {
+          let value = self.f[freg(&a[1])?];
+          self.set(&a[0], if value.is_nan() { i32::MAX } else { value as i32 })?;
+        },
+        "fadd.s" | "fsub.s" | "fmul.s" | "fdiv.s" => {
+          let (l, r) = (self.f[freg(&a[1])?], self.f[freg(&a[2])?]);
+          let value = match op.as_str() {
+            "fadd.s" => l + r,
+            "fsub.s" => l - r,
+            "fmul.s" => l * r,
+            _ => l / r,
+          };
+          // any nan computed is the canonical one
+          self.f[freg(&a[0])?] = if value.is_nan() { f32::from_bits(0x7fc0_0000) } else { value };
+        },
+        "feq.s" | "flt.s" | "fle.s" => {
+          let (l, r) = (self.f[freg(&a[1])?], self.f[freg(&a[2])?]);
+          let value = match op.as_str() {
+            "feq.s" => l == r,
+            "flt.s" => l < r,
+            _ => l <= r,
+          };
+          self.set(&a[0], value as i32)?;
+        },
+        _ => {
+          let value = alu(&op, self.get(&a[1])?, self.get(&a[2])?)?;
+          self.set(&a[0], value)?;
+        },
+      }
+    }
+    Err("step limit exceeded".into())
+  }
+
+  fn next_int(&mut self) -> Result<i32, String> {
+    while self.input.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+      self.pos += 1;
+    }
+    let start = self.pos;
+    if matches!(self.input.get(self.pos), Some(b'-' | b'+')) {
+      self.pos += 1;
+    }
+    while self.input.get(self.pos).is_some_and(u8::is_ascii_digit) {
+      self.pos += 1;
+    }
+    let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+    text.parse().map_err(|_| format!("bad int in input at {}", start))
+  }
+
+  // a float in decimal or in hex, as scanf reads it with `%a`
+  fn next_float(&mut self) -> Result<f32, String> {
+    while self.input.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+      self.pos += 1;
+    }
+    let start = self.pos;
+    while self.input.get(self.pos).is_some_and(|c| c.is_ascii_hexdigit() || b".xXpP+-".contains(c)) {
+      self.pos += 1;
+    }
+    let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+    let bad = || format!("bad float in input at {}", start);
+    let (sign, body) = match text.strip_prefix('-') {
+      Some(body) => (-1.0, body),
+      None => (1.0, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value = match body.strip_prefix("0x").or(body.strip_prefix("0X")) {
+      Some(hex) => {
+        let (mantissa, exp) = hex.split_once(['p', 'P']).unwrap_or((hex, "0"));
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let mut value = 0.0;
+        for c in int.chars().chain(frac.chars()) {
+          value = value * 16.0 + c.to_digit(16).ok_or_else(bad)? as f64;
+        }
+        let exp = exp.parse::<i32>().map_err(|_| bad())? - 4 * frac.len() as i32;
+        (value * 2f64.powi(exp)) as f32
+      },
+      None => body.parse().map_err(|_| bad())?,
+    };
+    Ok(sign * value)
+  }
+
+  fn runtime(&mut self, name: &str) -> Result<(), String> {
+    let (a0, a1) = (self.get("a0")?, self.get("a1")?);
+    let fa0 = self.f[freg("fa0")?];
+    let mut result = None;
+    let mut fresult = None;
+    match name {
+      "getint" => result = Some(self.next_int()?),
+      "getch" => {
+        let ch = self.input.get(self.pos).map_or(-1, |&c| c as i32);
+        self.pos += 1;
+        result = Some(ch);
+      },
+      "getarray" => {
+        let n = self.next_int()?;
+        for i in 0..n {
+          let value = self.next_int()?;
+          self.store_word((a0 + 4 * i) as usize, value)?;
+        }
+        result = Some(n);
+      },
+      "getfloat" => fresult = Some(self.next_float()?),
+      "getfarray" => {
+        let n = self.next_int()?;
+        for i in 0..n {
+          let value = self.next_float()?;
+          self.store_word((a0 + 4 * i) as usize, value.to_bits() as i32)?;
+        }
+        result = Some(n);
+      },
+      "putint" => self.output += &a0.to_string(),
+      "putfloat" => self.output += &hex_float(fa0 as f64),
+      "putch" => self.output.push(a0 as u8 as char),
+      "putarray" => {
+        self.output += &format!("{}:", a0);
+        for i in 0..a0 {
+          self.output += &format!(" {}", self.load_word((a1 + 4 * i) as usize)?);
+        }
+        self.output.push('\n');
+      },
+      "putfarray" => {
+        self.output += &format!("{}:", a0);
+        for i in 0..a0 {
+          let value = f32::from_bits(self.load_word((a1 + 4 * i) as usize)? as u32);
+          self.output += &format!(" {}", hex_float(value as f64));
+        }
+        self.output.push('\n');
+      },
+      "putf" => self.putf()?,
+      "__extendsfdf2" => {
+        let bits = (self.f[freg("fa0")?] as f64).to_bits();
+        self.set("a1", (bits >> 32) as i32)?;
+        result = Some(bits as i32);
+      },
+      "starttime" | "stoptime" => {},
+      _ => return Err(format!("unknown function `{}`", name)),
+    }
+    let a1 = self.get("a1")?;
+    for r in CLOBBERED {
+      self.set(r, GARBAGE)?;
+    }
+    for r in FCLOBBERED {
+      self.f[freg(r)?] = f32::from_bits(GARBAGE as u32);
+    }
+    self.f[freg("fa0")?] = fresult.unwrap_or(f32::from_bits(GARBAGE as u32));
+    if name == "__extendsfdf2" {
+      self.set("a1", a1)?;
+    }
+    self.set("a0", result.unwrap_or(GARBAGE))
+  }
+
+  // the format in a0, args in a1.. then on the stack,
+  // a double for `%f` in two words from an even one
+  fn putf(&mut self) -> Result<(), String> {
+    let mut addr = self.get("a0")? as usize;
+    let mut format = Vec::new();
+    while self.mem[addr] != 0 {
+      format.push(self.mem[addr]);
+      addr += 1;
+    }
+    let mut next = 1;
+    let mut chars = format.into_iter();
+    while let Some(c) = chars.next() {
+      if c != b'%' {
+        self.output.push(c as char);
+        continue;
+      }
+      let conv = chars.next().ok_or("format ends in `%`")?;
+      match conv {
+        b'%' => self.output.push('%'),
+        b'd' => {
+          self.output += &self.vararg(next)?.to_string();
+          next += 1;
+        },
+        b'c' => {
+          self.output.push(self.vararg(next)? as u8 as char);
+          next += 1;
+        },
+        b'f' => {
+          next += next % 2;
+          let (lo, hi) = (self.vararg(next)? as u32 as u64, self.vararg(next + 1)? as u32 as u64);
+          self.output += &format!("{:.6}", f64::from_bits(hi << 32 | lo));
+          next += 2;
+        },
+        _ => return Err(format!("unsupported conversion `%{}`", conv as char)),
+      }
+    }
+    Ok(())
+  }
+
+  // the word of the [i]th arg of a variadic call
+  fn vararg(&self, i: usize) -> Result<i32, String> {
+    if i < 8 {
+      self.get(&format!("a{}", i))
+    } else {
+      self.load_word(self.get("sp")? as usize + 4 * (i - 8))
+    }
+  }
+}
+
+// a double as printf writes it with `%a`, floats are normal ones as doubles
+fn hex_float(v: f64) -> String {
+  let sign = if v.is_sign_negative() { "-" } else { "" };
+  if v.is_nan() {
+    return format!("{}nan", sign);
+  } else if v.is_infinite() {
+    return format!("{}inf", sign);
+  } else if v == 0.0 {
+    return format!("{}0x0p+0", sign);
+  }
+  let bits = v.to_bits();
+  let exp = ((bits >> 52) & 0x7ff) as i64 - 1023;
+  let digits = format!("{:013x}", bits & ((1 << 52) - 1));
+  let digits = digits.trim_end_matches('0');
+  let dot = if digits.is_empty() { "" } else { "." };
+  format!("{}0x1{}{}p{:+}", sign, dot, digits, exp)
+}
+
+// integer operations with the results the hardware gives
+fn alu(op: &str, l: i32, r: i32) -> Result<i32, String> {
+  Ok(match op {
+    "add" => l.wrapping_add(r),
+    "sub" => l.wrapping_sub(r),
+    "mul" => l.wrapping_mul(r),
+    "div" => if r == 0 { -1 } else { l.wrapping_div(r) },
+    "rem" => if r == 0 { l } else { l.wrapping_rem(r) },
+    "and" => l & r,
+    "or" => l | r,
+    "xor" => l ^ r,
+    "sll" => l.wrapping_shl(r as u32),
+    "srl" => (l as u32).wrapping_shr(r as u32) as i32,
+    "sra" => l.wrapping_shr(r as u32),
+    "slt" => (l < r) as i32,
+    "sgt" => (l > r) as i32,
+    _ => return Err(format!("unknown instruction `{}`", op)),
+  })
+}